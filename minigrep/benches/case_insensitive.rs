@@ -0,0 +1,35 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use minigrep::search_case_insensitive;
+
+// 改动之前的实现：每一行都调用 to_lowercase 分配一个新的 String
+fn search_to_lowercase<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    let query = query.to_lowercase();
+    let mut results = Vec::new();
+    for line in contents.lines() {
+        if line.to_lowercase().contains(&query) {
+            results.push(line);
+        }
+    }
+    results
+}
+
+fn bench_case_insensitive(c: &mut Criterion) {
+    // 把 poem.txt 重复多次，得到一个中英文混合、足够大的输入
+    let poem = include_str!("../poem.txt");
+    let contents = format!("{poem}\n").repeat(2000);
+
+    let mut group = c.benchmark_group("case_insensitive");
+    for query in ["NOBODY", "somebody!", "不属于"] {
+        group.bench_function(format!("to_lowercase/{query}"), |b| {
+            b.iter(|| search_to_lowercase(black_box(query), black_box(&contents)))
+        });
+        group.bench_function(format!("case_folding/{query}"), |b| {
+            b.iter(|| search_case_insensitive(black_box(query), black_box(&contents)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_case_insensitive);
+criterion_main!(benches);
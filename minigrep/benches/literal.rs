@@ -0,0 +1,125 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+use minigrep::{search, Line, PatternKind, SearcherBuilder, Sink};
+
+// 改动之前的实现：先按行切分，再对每一行调用 contains
+fn search_lines<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    let mut results = Vec::new();
+    for line in contents.lines() {
+        if line.contains(query) {
+            results.push(line);
+        }
+    }
+    results
+}
+
+// 命令行走的是 Searcher 的流式路径，只数选中的行，不计输出的开销
+struct Count(usize);
+
+impl Sink for Count {
+    fn matched(&mut self, _line: &Line) -> std::io::Result<bool> {
+        self.0 += 1;
+        Ok(true)
+    }
+}
+
+// 类似日志的合成语料：大约 size 字节，每 1000 行有一行包含 "connection reset"
+// 用固定种子的线性同余生成器，每次运行得到同样的内容
+fn synthetic(size: usize) -> String {
+    const WORDS: &[&str] = &[
+        "request",
+        "handled",
+        "user",
+        "session",
+        "cache",
+        "miss",
+        "hit",
+        "latency",
+        "ms",
+        "GET",
+        "POST",
+        "/api/v1/items",
+        "200",
+        "404",
+        "worker",
+        "queue",
+        "retry",
+        "timeout",
+    ];
+    let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut out = String::with_capacity(size + 128);
+    let mut line = 0;
+    while out.len() < size {
+        line += 1;
+        out.push_str("2024-05-01T12:00:00Z INFO");
+        for _ in 0..10 {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            out.push(' ');
+            out.push_str(WORDS[(seed >> 33) as usize % WORDS.len()]);
+        }
+        if line % 1000 == 0 {
+            out.push_str(" connection reset");
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn bench_literal(c: &mut Criterion) {
+    let poem = include_str!("../poem.txt");
+    let corpora = [
+        ("poem", format!("{poem}\n").repeat(2000)),
+        ("synthetic_32mb", synthetic(32 << 20)),
+    ];
+    // 常见的词、少见的词，以及完全不出现的词（最能体现跳过整行的收益）
+    let queries = [
+        ("poem", ["nobody", "bog", "zebra"]),
+        (
+            "synthetic_32mb",
+            ["latency", "connection reset", "segfault"],
+        ),
+    ];
+
+    for ((name, contents), (_, queries)) in corpora.iter().zip(queries) {
+        let mut group = c.benchmark_group(format!("literal/{name}"));
+        group.throughput(Throughput::Bytes(contents.len() as u64));
+        if contents.len() > 1 << 20 {
+            group.sample_size(20);
+        }
+        for query in queries {
+            assert_eq!(search_lines(query, contents), search(query, contents));
+            group.bench_function(format!("lines_contains/{query}"), |b| {
+                b.iter(|| search_lines(black_box(query), black_box(contents)))
+            });
+            group.bench_function(format!("memmem/{query}"), |b| {
+                b.iter(|| search(black_box(query), black_box(contents)))
+            });
+            // 逐行判断和整块缓冲区预过滤的对比：正则不走预过滤
+            for (name, kind) in [
+                ("searcher_regex", PatternKind::Regex),
+                ("searcher_prefilter", PatternKind::Literal),
+            ] {
+                let searcher = SearcherBuilder::new()
+                    .pattern(query)
+                    .kind(kind)
+                    .build()
+                    .unwrap();
+                group.bench_function(format!("{name}/{query}"), |b| {
+                    b.iter(|| {
+                        let mut count = Count(0);
+                        searcher
+                            .search_reader(black_box(contents.as_bytes()), &mut count)
+                            .unwrap();
+                        count.0
+                    })
+                });
+            }
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_literal);
+criterion_main!(benches);
@@ -0,0 +1,78 @@
+#!/usr/bin/env python3
+# 根据 Unicode 的 CaseFolding.txt 生成 src/fold_table.rs
+#
+#   curl -O https://www.unicode.org/Public/14.0.0/ucd/CaseFolding.txt
+#   python3 scripts/gen_fold_table.py CaseFolding.txt > src/fold_table.rs
+#
+# 地址里固定了 Unicode 版本，重新生成得到的是同一张表；升级时改掉地址里的版本号，
+# 生成的文件开头会记下实际读到的版本
+# 生成的表只包含非 ASCII 字符（ASCII 在 fold.rs 里走快速路径），
+# 土耳其语的 T 规则只涉及 I 和 İ 两个字符，由 fold.rs 直接处理
+import re
+import sys
+
+CASELESS = [(0x2E80, 0xA63F), (0xAC00, 0xD7FF)]
+
+
+def caseless(cp):
+    return any(lo <= cp <= hi for lo, hi in CASELESS)
+
+
+def char(cp):
+    return "'\\u{%x}'" % cp
+
+
+def main(path):
+    version = None
+    simple, full, turkic = {}, {}, {}
+    with open(path, encoding="utf-8") as f:
+        for line in f:
+            if version is None:
+                m = re.search(r"CaseFolding-(\d+\.\d+\.\d+)", line)
+                if m:
+                    version = m.group(1)
+            data = line.split("#", 1)[0].strip()
+            if not data:
+                continue
+            code, status, mapping = [field.strip() for field in data.split(";")[:3]]
+            cp = int(code, 16)
+            target = [int(c, 16) for c in mapping.split()]
+            if status in "CS":
+                simple[cp] = target[0]
+            if status in "CF":
+                full[cp] = target
+            if status == "T":
+                turkic[cp] = target[0]
+
+    assert version, "%s 里没有 CaseFolding-x.y.z 版本行" % path
+    assert set(turkic) == {0x49, 0x130}, "fold.rs 只处理 I 和 İ 的土耳其语规则"
+    entries = []
+    lead_bytes = set()
+    for cp in sorted(set(simple) | set(full)):
+        if cp < 0x80:
+            continue
+        s = simple.get(cp, cp)
+        f = full.get(cp, [s])
+        targets = [s] + f
+        # fold.rs 里认为这些区间的字符既不会被折叠，也不是任何字符的折叠结果
+        assert not caseless(cp) and not any(caseless(t) for t in targets), hex(cp)
+        if s < 0x80 or f[0] < 0x80:
+            lead_bytes.add(chr(cp).encode("utf-8")[0])
+        entries.append((cp, s, [] if f == [s] else f))
+
+    out = sys.stdout
+    out.write("// 由 scripts/gen_fold_table.py 根据 Unicode %s 的 CaseFolding.txt 生成，不要手工修改\n\n"
+              % version)
+    out.write("// (字符, C+S 简单折叠, C+F 完整折叠)，按字符排序；完整折叠和简单折叠相同时为空\n")
+    out.write("pub(crate) const CASE_FOLDING: &[(char, char, &[char])] = &[\n")
+    for cp, s, f in entries:
+        full_chars = ", ".join(char(c) for c in f)
+        out.write("    (%s, %s, &[%s]),\n" % (char(cp), char(s), full_chars))
+    out.write("];\n\n")
+    out.write("// 折叠结果以 ASCII 字符开头的非 ASCII 字符（ſ、K、İ、ǰ、ẖ、ﬁ 等）的 UTF-8 首字节\n")
+    out.write("pub(crate) const ASCII_FOLD_LEAD_BYTES: [u8; %d] = [%s];\n"
+              % (len(lead_bytes), ", ".join("0x%02x" % b for b in sorted(lead_bytes))))
+
+
+if __name__ == "__main__":
+    main(sys.argv[1])
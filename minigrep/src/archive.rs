@@ -0,0 +1,297 @@
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Cursor, Read};
+use std::path::{Path, PathBuf};
+
+use flate2::read::MultiGzDecoder;
+use ruzstd::decoding::{FrameDecoder, StreamingDecoder};
+
+use crate::MinigrepError;
+
+// 压缩流和 tar 可以互相嵌套（例如 tar.gz 里的 .zst），超过这个深度就当作普通文件
+const MAX_DEPTH: usize = 4;
+
+// 识别压缩格式和 tar 需要看的最多字节数：tar 的 "ustar" 标记在第 257 字节
+const SNIFF_LEN: u64 = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Compression {
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+impl Compression {
+    // 按文件开头的魔数识别，不看扩展名
+    fn detect(head: &[u8]) -> Option<Compression> {
+        if head.starts_with(&[0x1f, 0x8b]) {
+            Some(Compression::Gzip)
+        } else if head.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Compression::Zstd)
+        } else if head.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0]) {
+            Some(Compression::Xz)
+        } else {
+            None
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+            Compression::Xz => "xz",
+        }
+    }
+}
+
+// -z：把 path 解压（如果是压缩流）并展开 tar，对其中每个普通文件调用 f
+// f 的参数依次是输出时使用的名字、是否位于 tar 里面、解压后的内容
+// tar 里的文件名字是 "archive.tar.gz:inner/path"；单独的压缩文件仍然使用原来的路径
+pub fn for_each_entry<F>(path: &Path, f: &mut F) -> Result<(), MinigrepError>
+where
+    F: FnMut(&Path, bool, &mut dyn Read) -> Result<(), MinigrepError>,
+{
+    let reader: Box<dyn Read> = if path == Path::new("-") {
+        Box::new(io::stdin().lock())
+    } else {
+        Box::new(File::open(path).map_err(|err| MinigrepError::io(path, err))?)
+    };
+    visit(path, false, reader, 0, f)
+}
+
+fn visit<F>(
+    name: &Path,
+    in_archive: bool,
+    reader: Box<dyn Read + '_>,
+    depth: usize,
+    f: &mut F,
+) -> Result<(), MinigrepError>
+where
+    F: FnMut(&Path, bool, &mut dyn Read) -> Result<(), MinigrepError>,
+{
+    let error = |err| MinigrepError::io(name, err);
+    // 先读出开头的一段用来识别格式，再把它接回剩下的内容前面
+    let mut head = Vec::new();
+    let mut reader = reader;
+    reader
+        .by_ref()
+        .take(SNIFF_LEN)
+        .read_to_end(&mut head)
+        .map_err(error)?;
+    let mut reader: Box<dyn Read + '_> = Box::new(Cursor::new(head.clone()).chain(reader));
+    if depth >= MAX_DEPTH {
+        return f(name, in_archive, &mut reader);
+    }
+
+    if let Some(compression) = Compression::detect(&head) {
+        let decompressed = decompress(compression, reader).map_err(error)?;
+        return visit(name, in_archive, decompressed, depth + 1, f);
+    }
+
+    if !is_tar(&head) {
+        return f(name, in_archive, &mut reader);
+    }
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries().map_err(error)? {
+        let entry = entry.map_err(error)?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let inner = entry.path().map_err(error)?.into_owned();
+        let mut display = OsString::from(name);
+        display.push(":");
+        display.push(&inner);
+        visit(&PathBuf::from(display), true, Box::new(entry), depth + 1, f)?;
+    }
+    Ok(())
+}
+
+// POSIX 和 GNU 格式的 tar 头部在 257 字节处都有 "ustar" 标记
+fn is_tar(head: &[u8]) -> bool {
+    head.get(257..262) == Some(b"ustar")
+}
+
+fn decompress<'a>(
+    compression: Compression,
+    reader: Box<dyn Read + 'a>,
+) -> io::Result<Box<dyn Read + 'a>> {
+    let decompressed: Box<dyn Read + 'a> = match compression {
+        Compression::Gzip => Box::new(MultiGzDecoder::new(reader)),
+        Compression::Zstd => Box::new(ZstdFrames::new(BufReader::new(reader))?),
+        // lzma-rs 只有往 Write 里解压的接口，xz 的内容整个解压到内存里
+        Compression::Xz => {
+            let mut out = Vec::new();
+            lzma_rs::xz_decompress(&mut BufReader::new(reader), &mut out)
+                .map_err(|err| corrupt(compression, err))?;
+            Box::new(Cursor::new(out))
+        }
+    };
+    Ok(Box::new(Corrupt {
+        inner: decompressed,
+        compression,
+    }))
+}
+
+// 解压失败通常报 InvalidData，会被当成“不是合法的 UTF-8”，这里换成更准确的说法
+fn corrupt(compression: Compression, err: impl std::fmt::Display) -> io::Error {
+    io::Error::other(format!("{} data is corrupt: {err}", compression.name()))
+}
+
+struct Corrupt<R> {
+    inner: R,
+    compression: Compression,
+}
+
+impl<R: Read> Read for Corrupt<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf).map_err(|err| match err.kind() {
+            io::ErrorKind::InvalidData
+            | io::ErrorKind::InvalidInput
+            | io::ErrorKind::UnexpectedEof => corrupt(self.compression, err),
+            _ => err,
+        })
+    }
+}
+
+// ruzstd 的 StreamingDecoder 一次只解一帧，zstd 文件可以由多帧拼接而成
+struct ZstdFrames<R: BufRead> {
+    decoder: Option<StreamingDecoder<R, FrameDecoder>>,
+}
+
+impl<R: BufRead> ZstdFrames<R> {
+    fn new(reader: R) -> io::Result<ZstdFrames<R>> {
+        let decoder = StreamingDecoder::new(reader).map_err(io::Error::other)?;
+        Ok(ZstdFrames {
+            decoder: Some(decoder),
+        })
+    }
+}
+
+impl<R: BufRead> Read for ZstdFrames<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let Some(decoder) = &mut self.decoder else {
+                return Ok(0);
+            };
+            let read = decoder.read(buf)?;
+            if read > 0 || buf.is_empty() {
+                return Ok(read);
+            }
+            // 当前帧结束；输入里还有数据就接着解下一帧
+            let mut input = self.decoder.take().map(|d| d.into_inner()).unwrap();
+            if input.fill_buf()?.is_empty() {
+                return Ok(0);
+            }
+            self.decoder = Some(StreamingDecoder::new(input).map_err(io::Error::other)?);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+
+    fn entries(path: &Path) -> Vec<(String, bool, String)> {
+        let mut found = Vec::new();
+        for_each_entry(path, &mut |name, in_archive, reader| {
+            let mut text = String::new();
+            reader.read_to_string(&mut text).unwrap();
+            found.push((name.display().to_string(), in_archive, text));
+            Ok(())
+        })
+        .unwrap();
+        found
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn descends_into_tar_gz() {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, text) in [("logs/a.log", "error one\n"), ("b.txt", "fine\n")] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(text.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, text.as_bytes())
+                .unwrap();
+        }
+        let tarball = gzip(&builder.into_inner().unwrap());
+
+        let path = std::env::temp_dir().join(format!("minigrep-{}.tar.gz", std::process::id()));
+        std::fs::write(&path, tarball).unwrap();
+        let found = entries(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let name = path.display();
+        assert_eq!(
+            vec![
+                (
+                    format!("{name}:logs/a.log"),
+                    true,
+                    "error one\n".to_string()
+                ),
+                (format!("{name}:b.txt"), true, "fine\n".to_string()),
+            ],
+            found
+        );
+    }
+
+    #[test]
+    fn decompresses_single_streams() {
+        let text = b"first line\nsecond line\n";
+        let mut xz = Vec::new();
+        lzma_rs::xz_compress(&mut &text[..], &mut xz).unwrap();
+        let zstd = ruzstd::encoding::compress_to_vec(
+            &text[..],
+            ruzstd::encoding::CompressionLevel::Fastest,
+        );
+        // 两帧拼在一起也要能完整读出来
+        let zstd = [zstd.clone(), zstd].concat();
+
+        let dir = std::env::temp_dir();
+        let pid = std::process::id();
+        for (ext, data, expected) in [
+            ("gz", gzip(text), text.to_vec()),
+            ("xz", xz, text.to_vec()),
+            ("zst", zstd, text.repeat(2)),
+            ("txt", text.to_vec(), text.to_vec()),
+        ] {
+            let path = dir.join(format!("minigrep-{pid}.{ext}"));
+            std::fs::write(&path, data).unwrap();
+            let found = entries(&path);
+            std::fs::remove_file(&path).unwrap();
+            assert_eq!(
+                vec![(
+                    path.display().to_string(),
+                    false,
+                    String::from_utf8(expected).unwrap()
+                )],
+                found
+            );
+        }
+    }
+
+    #[test]
+    fn reports_corrupt_data() {
+        let path = std::env::temp_dir().join(format!("minigrep-{}-bad.gz", std::process::id()));
+        std::fs::write(&path, [0x1f, 0x8b, 8, 0, 1, 2, 3]).unwrap();
+        let err = for_each_entry(&path, &mut |_, _, reader| {
+            reader
+                .read_to_end(&mut Vec::new())
+                .map_err(|err| MinigrepError::io("x", err))?;
+            Ok(())
+        })
+        .unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(err.to_string().contains("gzip data is corrupt"), "{err}");
+    }
+}
@@ -0,0 +1,633 @@
+use std::env;
+use std::path::PathBuf;
+
+use encoding_rs::Encoding;
+
+use crate::{CaseFolding, ColorChoice, Config, MinigrepError, OutputMode, Scope, SortMode};
+
+pub const USAGE: &str = "\
+Usage: minigrep [OPTIONS] QUERY [PATH]...
+       minigrep [OPTIONS] -e PATTERN... [PATH]...
+       minigrep [OPTIONS] -f FILE... [PATH]...
+       minigrep index build DIR
+
+Search for QUERY in each PATH. Directories are searched recursively.
+With no PATH, or when PATH is -, read standard input.
+'index build' creates or updates a trigram index of DIR for --index.
+
+Options:
+  -e, --regexp PATTERN       Search for PATTERN; may be given many times
+  -f, --file FILE            Read patterns from FILE, one per line
+      --show-pattern         Print which pattern(s) matched before each line
+  -i, --ignore-case          Match case insensitively (also enabled by IGNORE_CASE)
+  -s, --case-sensitive       Match case sensitively, overriding IGNORE_CASE
+      --case-folding MODE    Folding used by -i: 'full' (default), 'simple' or 'turkic'
+  -E, --regex                Treat QUERY as a regular expression
+      --fuzzy K              Match QUERY with up to K edits (insertions, deletions,
+                             substitutions) and print each line's best distance
+      --bool                 Treat QUERY as a boolean query: terms joined by AND,
+                             OR, NOT, NEAR/N (at most N words apart) and parentheses,
+                             e.g. 'timeout AND retry AND NOT debug'
+      --scope SCOPE          Match per 'line' (default) or per 'paragraph' (lines
+                             between blank lines; all lines of a match are printed)
+  -U, --multiline            Let matches span lines and print every line a match
+                             covers; -m then counts matches (with -E, ^ and $
+                             match at line boundaries and \n matches newlines)
+  -w, --word-regexp          Only match whole words
+  -v, --invert-match         Select non-matching lines
+  -m, --max-count NUM        Stop after NUM selected lines per file
+  -c, --count                Print only a count of selected lines per file
+  -l, --files-with-matches   Print only names of files with selected lines
+      --color WHEN           Highlight matches: 'auto' (default), 'always' or 'never'
+      --json                 Print results as JSON Lines (begin/match/end/summary)
+  -n, --line-number          Print line numbers
+  -b, --byte-offset          Print the byte offset of each line; for input
+                             decoded from a BOM or --encoding, offsets (also
+                             in --json) count bytes of the decoded UTF-8 text
+  -A, --after-context NUM    Print NUM lines of trailing context
+  -B, --before-context NUM   Print NUM lines of leading context
+  -C, --context NUM          Print NUM lines of leading and trailing context
+  -r, --replace TEXT         Print lines with every match replaced by TEXT
+                             ($1, ${name} refer to capture groups with -E)
+      --in-place[=SUFFIX]    Rewrite files with --replace, keeping a backup
+                             with SUFFIX when given; binary (unless -a) and
+                             non-UTF-8 files are skipped with a warning
+      --dry-run              With --in-place, print a diff instead of writing
+  -a, --text                 Search binary files as if they were text
+  -z, --search-zip           Decompress gzip, zstd and xz input and search each
+                             file inside tar archives (shown as ARCHIVE:PATH)
+      --encoding NAME        Decode input as NAME (e.g. utf-16le, latin1);
+                             default: UTF-8, or UTF-16 when a BOM is present
+  -j, --threads NUM          Search files with NUM worker threads (default: CPU count)
+      --sort MODE            Output order: 'none' (as files finish), 'path', or
+                             'distance' (with --fuzzy: closest lines first per file)
+      --include GLOB         Only search files matching GLOB
+      --exclude GLOB         Skip files and directories matching GLOB
+      --follow               Keep reading files as they grow, like 'tail -f';
+                             handles truncation and log rotation
+      --watch DIR            Search DIR, then search it again whenever a file
+                             in it is added, removed or modified
+      --index                Only search files that the index of each directory
+                             says may match; changed files are always searched
+      --help                 Print this help and exit
+  -V, --version              Print version and exit
+";
+
+// 命令行解析的结果：要么执行一次搜索，要么只是打印帮助/版本信息
+pub enum Command {
+    // Config 比较大，装箱避免 Command 的各个变体大小相差太多
+    Search(Box<Config>),
+    // minigrep index build DIR
+    IndexBuild(PathBuf),
+    Help,
+    Version,
+}
+
+impl Command {
+    // args 的第一个元素是程序路径，会被跳过；参数错误都是 MinigrepError::Usage
+    pub fn parse(args: &[String]) -> Result<Command, MinigrepError> {
+        Command::parse_args(args).map_err(MinigrepError::Usage)
+    }
+
+    fn parse_args(args: &[String]) -> Result<Command, String> {
+        // 只有 "index build" 才是子命令，"minigrep index FILE" 仍然是搜索 index
+        if let [_, index, build, rest @ ..] = args {
+            if index == "index" && build == "build" {
+                return match rest {
+                    [dir] => Ok(Command::IndexBuild(PathBuf::from(dir))),
+                    _ => Err("usage: minigrep index build DIR".to_string()),
+                };
+            }
+        }
+
+        let mut config = Config {
+            // 环境变量只是默认值，-i/-s 会覆盖它
+            ignore_case: env::var("IGNORE_CASE").is_ok(),
+            ..Config::default()
+        };
+        let mut positional = Vec::new();
+        let mut args = args.iter().skip(1);
+
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                // "--" 之后的参数都当作位置参数，方便搜索以 - 开头的内容
+                positional.extend(args.by_ref().cloned());
+                break;
+            }
+
+            if let Some(long) = arg.strip_prefix("--") {
+                // 同时支持 --name value 和 --name=value 两种写法
+                let (name, inline) = match long.split_once('=') {
+                    Some((name, value)) => (name, Some(value.to_string())),
+                    None => (long, None),
+                };
+                let option = find_option(|o| o.long == name)
+                    .ok_or_else(|| format!("unknown option '--{name}'"))?;
+                let value = match (option.takes, inline) {
+                    (Takes::Value, Some(value)) => Some(value),
+                    (Takes::Value, None) => {
+                        Some(args.next().cloned().ok_or_else(|| missing_value(arg))?)
+                    }
+                    // 可选值只能用 --name=value 的写法给出
+                    (Takes::OptionalValue, inline) => inline,
+                    (Takes::Nothing, Some(_)) => {
+                        return Err(format!("option '--{name}' doesn't take a value"))
+                    }
+                    (Takes::Nothing, None) => None,
+                };
+                if let Some(command) = apply(&mut config, option, value.as_deref())? {
+                    return Ok(command);
+                }
+            } else if arg.len() > 1 && arg.starts_with('-') {
+                // 短选项可以合并写（-in），带值的短选项可以紧跟数值（-A3）
+                let cluster = &arg[1..];
+                for (i, c) in cluster.char_indices() {
+                    let option = find_option(|o| o.short == Some(c))
+                        .ok_or_else(|| format!("unknown option '-{c}'"))?;
+                    if option.takes != Takes::Value {
+                        if let Some(command) = apply(&mut config, option, None)? {
+                            return Ok(command);
+                        }
+                        continue;
+                    }
+                    let rest = &cluster[i + c.len_utf8()..];
+                    let value = if rest.is_empty() {
+                        args.next()
+                            .cloned()
+                            .ok_or_else(|| missing_value(&format!("-{c}")))?
+                    } else {
+                        rest.to_string()
+                    };
+                    apply(&mut config, option, Some(&value))?;
+                    break;
+                }
+            } else {
+                positional.push(arg.clone());
+            }
+        }
+
+        let mut positional = positional.into_iter();
+        // 给出了 -e/-f 时，所有位置参数都是路径
+        if config.patterns.is_empty() && config.pattern_files.is_empty() {
+            config.query = positional
+                .next()
+                .ok_or("missing QUERY argument (see --help)")?;
+        }
+        config.file_paths = positional.collect();
+
+        if config.in_place {
+            if config.replace.is_none() {
+                return Err("--in-place requires --replace".to_string());
+            }
+            if config.invert {
+                return Err("--in-place can't be combined with --invert-match".to_string());
+            }
+            if config.file_paths.is_empty() || config.file_paths.iter().any(|p| p == "-") {
+                return Err("--in-place needs file or directory paths".to_string());
+            }
+            if config.search_zip {
+                return Err("--in-place can't rewrite compressed files".to_string());
+            }
+        } else if config.dry_run {
+            return Err("--dry-run only makes sense with --in-place".to_string());
+        }
+
+        if config.multiline && (config.fuzzy.is_some() || config.replace.is_some() || config.follow)
+        {
+            return Err("-U can't be combined with --fuzzy, --replace or --follow".to_string());
+        }
+
+        if config.boolean
+            && (config.regex
+                || config.fuzzy.is_some()
+                || !config.patterns.is_empty()
+                || !config.pattern_files.is_empty())
+        {
+            return Err(
+                "--bool takes a single QUERY and can't be combined with -E or --fuzzy".to_string(),
+            );
+        }
+        if config.scope == Scope::Paragraph
+            && (config.multiline
+                || config.fuzzy.is_some()
+                || config.replace.is_some()
+                || config.follow)
+        {
+            return Err(
+                "--scope paragraph can't be combined with -U, --fuzzy, --replace or --follow"
+                    .to_string(),
+            );
+        }
+
+        if config.fuzzy.is_some() {
+            if config.regex || !config.patterns.is_empty() || !config.pattern_files.is_empty() {
+                return Err("--fuzzy takes a single literal QUERY".to_string());
+            }
+        } else if config.sort == SortMode::Distance {
+            return Err("--sort distance requires --fuzzy".to_string());
+        }
+        if config.sort == SortMode::Distance
+            && (config.before_context > 0 || config.after_context > 0)
+        {
+            return Err("--sort distance can't be combined with context lines".to_string());
+        }
+
+        if config.follow {
+            if config.file_paths.is_empty() || config.file_paths.iter().any(|p| p == "-") {
+                return Err("--follow needs file paths".to_string());
+            }
+            if !matches!(config.output, OutputMode::Lines | OutputMode::Json)
+                || config.before_context > 0
+                || config.after_context > 0
+                || config.in_place
+                || config.search_zip
+                || config.sort == SortMode::Distance
+            {
+                return Err(
+                    "--follow can't be combined with -c, -l, context lines, -z, --in-place or --sort distance"
+                        .to_string(),
+                );
+            }
+        }
+        if let Some(dir) = &config.watch {
+            if !config.file_paths.is_empty() {
+                return Err("--watch DIR searches DIR; don't give other paths".to_string());
+            }
+            if config.follow || config.in_place {
+                return Err("--watch can't be combined with --follow or --in-place".to_string());
+            }
+            config.file_paths.push(dir.clone());
+        }
+
+        // 和 grep 一样，没有给出路径时从标准输入读取
+        if config.file_paths.is_empty() {
+            config.file_paths.push("-".to_string());
+        }
+
+        Ok(Command::Search(Box::new(config)))
+    }
+}
+
+struct Opt {
+    short: Option<char>,
+    long: &'static str,
+    takes: Takes,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Takes {
+    Nothing,
+    Value,
+    OptionalValue,
+}
+
+const fn flag(short: Option<char>, long: &'static str) -> Opt {
+    Opt {
+        short,
+        long,
+        takes: Takes::Nothing,
+    }
+}
+
+const fn value(short: Option<char>, long: &'static str) -> Opt {
+    Opt {
+        short,
+        long,
+        takes: Takes::Value,
+    }
+}
+
+const fn optional(long: &'static str) -> Opt {
+    Opt {
+        short: None,
+        long,
+        takes: Takes::OptionalValue,
+    }
+}
+
+const OPTIONS: &[Opt] = &[
+    value(Some('e'), "regexp"),
+    value(Some('f'), "file"),
+    flag(None, "show-pattern"),
+    flag(Some('i'), "ignore-case"),
+    flag(Some('s'), "case-sensitive"),
+    value(None, "case-folding"),
+    flag(Some('E'), "regex"),
+    value(None, "fuzzy"),
+    flag(None, "bool"),
+    value(None, "scope"),
+    flag(Some('U'), "multiline"),
+    flag(Some('w'), "word-regexp"),
+    flag(Some('v'), "invert-match"),
+    value(Some('m'), "max-count"),
+    flag(Some('c'), "count"),
+    flag(Some('l'), "files-with-matches"),
+    value(None, "color"),
+    flag(None, "json"),
+    flag(Some('n'), "line-number"),
+    flag(Some('b'), "byte-offset"),
+    value(Some('A'), "after-context"),
+    value(Some('B'), "before-context"),
+    value(Some('C'), "context"),
+    value(Some('r'), "replace"),
+    optional("in-place"),
+    flag(None, "dry-run"),
+    flag(Some('a'), "text"),
+    flag(Some('z'), "search-zip"),
+    value(None, "encoding"),
+    value(Some('j'), "threads"),
+    value(None, "sort"),
+    value(None, "include"),
+    value(None, "exclude"),
+    flag(None, "index"),
+    flag(None, "follow"),
+    value(None, "watch"),
+    flag(None, "help"),
+    flag(Some('V'), "version"),
+];
+
+fn find_option(pred: impl Fn(&Opt) -> bool) -> Option<&'static Opt> {
+    OPTIONS.iter().find(|o| pred(o))
+}
+
+fn missing_value(arg: &str) -> String {
+    format!("option '{arg}' requires a value")
+}
+
+fn parse_number(option: &Opt, value: &str) -> Result<usize, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid number '{value}' for '--{}'", option.long))
+}
+
+// 把一个选项写进 config；--help/--version 直接返回对应的 Command
+fn apply(
+    config: &mut Config,
+    option: &Opt,
+    value: Option<&str>,
+) -> Result<Option<Command>, String> {
+    let value = value.unwrap_or_default();
+    match option.long {
+        "regexp" => config.patterns.push(value.to_string()),
+        "file" => config.pattern_files.push(value.to_string()),
+        "show-pattern" => config.show_pattern = true,
+        "ignore-case" => config.ignore_case = true,
+        "case-sensitive" => config.ignore_case = false,
+        "case-folding" => {
+            config.case_folding = match value {
+                "full" => CaseFolding::Full,
+                "simple" => CaseFolding::Simple,
+                "turkic" => CaseFolding::Turkic,
+                _ => {
+                    return Err(format!(
+                        "invalid case folding '{value}' (expected 'full', 'simple' or 'turkic')"
+                    ))
+                }
+            }
+        }
+        "regex" => config.regex = true,
+        "fuzzy" => config.fuzzy = Some(parse_number(option, value)?),
+        "word-regexp" => config.word = true,
+        "invert-match" => config.invert = true,
+        "max-count" => config.max_count = Some(parse_number(option, value)?),
+        "count" => config.output = OutputMode::Count,
+        "files-with-matches" => config.output = OutputMode::FilesWithMatches,
+        "color" => {
+            config.color = match value {
+                "auto" => ColorChoice::Auto,
+                "always" => ColorChoice::Always,
+                "never" => ColorChoice::Never,
+                _ => {
+                    return Err(format!(
+                        "invalid color choice '{value}' (expected 'auto', 'always' or 'never')"
+                    ))
+                }
+            }
+        }
+        "json" => config.output = OutputMode::Json,
+        "line-number" => config.line_number = true,
+        "byte-offset" => config.byte_offset = true,
+        "after-context" => config.after_context = parse_number(option, value)?,
+        "before-context" => config.before_context = parse_number(option, value)?,
+        "context" => {
+            config.after_context = parse_number(option, value)?;
+            config.before_context = config.after_context;
+        }
+        "replace" => config.replace = Some(value.to_string()),
+        "in-place" => {
+            config.in_place = true;
+            config.backup_suffix = Some(value.to_string()).filter(|s| !s.is_empty());
+        }
+        "dry-run" => config.dry_run = true,
+        "text" => config.text = true,
+        "search-zip" => config.search_zip = true,
+        "encoding" => {
+            config.encoding = match value {
+                "auto" => None,
+                _ => Some(
+                    Encoding::for_label(value.as_bytes())
+                        .ok_or_else(|| format!("unknown encoding '{value}'"))?,
+                ),
+            }
+        }
+        "threads" => config.threads = parse_number(option, value)?,
+        "sort" => {
+            config.sort = match value {
+                "none" => SortMode::None,
+                "path" => SortMode::Path,
+                "distance" => SortMode::Distance,
+                _ => {
+                    return Err(format!(
+                        "invalid sort mode '{value}' (expected 'none', 'path' or 'distance')"
+                    ))
+                }
+            }
+        }
+        "include" => config.include.push(value.to_string()),
+        "exclude" => config.exclude.push(value.to_string()),
+        "index" => config.index = true,
+        "follow" => config.follow = true,
+        "multiline" => config.multiline = true,
+        "bool" => config.boolean = true,
+        "scope" => {
+            config.scope = match value {
+                "line" => Scope::Line,
+                "paragraph" => Scope::Paragraph,
+                _ => {
+                    return Err(format!(
+                        "invalid scope '{value}' (expected 'line' or 'paragraph')"
+                    ))
+                }
+            }
+        }
+        "watch" => config.watch = Some(value.to_string()),
+        "help" => return Ok(Some(Command::Help)),
+        "version" => return Ok(Some(Command::Version)),
+        _ => unreachable!("option table and apply are out of sync"),
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Config, String> {
+        let mut full = vec!["minigrep".to_string()];
+        full.extend(args.iter().map(|s| s.to_string()));
+        match Command::parse(&full).map_err(|err| err.to_string())? {
+            Command::Search(config) => Ok(*config),
+            _ => Err("not a search".to_string()),
+        }
+    }
+
+    #[test]
+    fn short_and_long_flags() {
+        let config = parse(&["-inA2", "--max-count=3", "-C", "1", "query", "a", "b"]).unwrap();
+
+        assert!(config.ignore_case);
+        assert!(config.line_number);
+        assert_eq!(Some(3), config.max_count);
+        assert_eq!((1, 1), (config.before_context, config.after_context));
+        assert_eq!("query", config.query);
+        assert_eq!(vec!["a", "b"], config.file_paths);
+    }
+
+    #[test]
+    fn threads_and_sort() {
+        let config = parse(&["-j4", "--sort", "path", "q", "src"]).unwrap();
+
+        assert_eq!(4, config.threads);
+        assert_eq!(SortMode::Path, config.sort);
+        assert!(parse(&["--sort=size", "q", "src"]).is_err());
+    }
+
+    #[test]
+    fn follow_and_watch() {
+        assert!(parse(&["--follow", "error", "app.log"]).unwrap().follow);
+        assert!(parse(&["--follow", "error"]).is_err());
+        assert!(parse(&["--follow", "-c", "error", "app.log"]).is_err());
+
+        let config = parse(&["--watch", "logs", "error"]).unwrap();
+        assert_eq!(vec!["logs"], config.file_paths);
+        assert!(parse(&["--watch", "logs", "error", "other"]).is_err());
+    }
+
+    #[test]
+    fn multiline_option() {
+        assert!(
+            parse(&["-U", "-E", r"fn \w+\(\n", "src"])
+                .unwrap()
+                .multiline
+        );
+        assert!(parse(&["-U", "-r", "x", "q"]).is_err());
+        assert!(parse(&["-U", "--fuzzy=1", "q"]).is_err());
+    }
+
+    #[test]
+    fn boolean_query_options() {
+        let config = parse(&["--bool", "--scope=paragraph", "a AND b", "notes"]).unwrap();
+        assert!(config.boolean);
+        assert_eq!(Scope::Paragraph, config.scope);
+        assert!(parse(&["--bool", "-E", "a AND b"]).is_err());
+        assert!(parse(&["--scope", "page", "q"]).is_err());
+        assert!(parse(&["--scope", "paragraph", "-U", "q"]).is_err());
+    }
+
+    #[test]
+    fn fuzzy_options() {
+        let config = parse(&["--fuzzy", "2", "--sort=distance", "q"]).unwrap();
+        assert_eq!(Some(2), config.fuzzy);
+        assert_eq!(SortMode::Distance, config.sort);
+
+        assert!(parse(&["--sort=distance", "q"]).is_err());
+        assert!(parse(&["--fuzzy=1", "-e", "a", "-e", "b"]).is_err());
+        assert!(parse(&["--fuzzy=1", "--sort=distance", "-C1", "q"]).is_err());
+    }
+
+    #[test]
+    fn color_choice() {
+        assert_eq!(ColorChoice::Auto, parse(&["q"]).unwrap().color);
+        assert_eq!(
+            ColorChoice::Never,
+            parse(&["--color=never", "q"]).unwrap().color
+        );
+        assert!(parse(&["--color", "sometimes", "q"]).is_err());
+    }
+
+    #[test]
+    fn repeated_patterns() {
+        let config = parse(&["-e", "timeout", "-eretry", "-f", "ids.txt", "src"]).unwrap();
+
+        assert_eq!(vec!["timeout", "retry"], config.patterns);
+        assert_eq!(vec!["ids.txt"], config.pattern_files);
+        assert_eq!("", config.query);
+        assert_eq!(vec!["src"], config.file_paths);
+    }
+
+    #[test]
+    fn in_place_options() {
+        let config = parse(&["-r", "bar", "--in-place=.bak", "foo", "src"]).unwrap();
+        assert_eq!(Some("bar".to_string()), config.replace);
+        assert!(config.in_place);
+        assert_eq!(Some(".bak".to_string()), config.backup_suffix);
+
+        let config = parse(&["--replace=bar", "--in-place", "--dry-run", "foo", "src"]).unwrap();
+        assert_eq!(None, config.backup_suffix);
+        assert!(config.dry_run);
+
+        assert!(parse(&["--in-place", "foo", "src"]).is_err());
+        assert!(parse(&["--dry-run", "foo", "src"]).is_err());
+    }
+
+    #[test]
+    fn encoding_labels() {
+        let config = parse(&["--encoding", "latin1", "q"]).unwrap();
+        assert_eq!(Some(encoding_rs::WINDOWS_1252), config.encoding);
+        assert!(parse(&["--encoding=klingon", "q"]).is_err());
+    }
+
+    #[test]
+    fn defaults_to_stdin() {
+        assert_eq!(vec!["-"], parse(&["q"]).unwrap().file_paths);
+    }
+
+    #[test]
+    fn double_dash_ends_options() {
+        let config = parse(&["--", "-v", "poem.txt"]).unwrap();
+
+        assert!(!config.invert);
+        assert_eq!("-v", config.query);
+    }
+
+    #[test]
+    fn usage_errors() {
+        assert_eq!(
+            Err("unknown option '--frobnicate'".to_string()),
+            parse(&["--frobnicate"]).map(|_| ())
+        );
+        assert_eq!(
+            Err("option '-m' requires a value".to_string()),
+            parse(&["q", "-m"]).map(|_| ())
+        );
+        assert_eq!(
+            Err("invalid number 'x' for '--after-context'".to_string()),
+            parse(&["-A", "x", "q", "p"]).map(|_| ())
+        );
+        assert_eq!(
+            Err("missing QUERY argument (see --help)".to_string()),
+            parse(&[]).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn help_and_version() {
+        let args: Vec<String> = ["minigrep", "q", "--help"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert!(matches!(Command::parse(&args), Ok(Command::Help)));
+        let args: Vec<String> = ["minigrep", "-V"].iter().map(|s| s.to_string()).collect();
+        assert!(matches!(Command::parse(&args), Ok(Command::Version)));
+    }
+}
@@ -0,0 +1,280 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::{stream, Matcher, MinigrepError};
+
+// --in-place 的选项
+pub struct EditOptions<'a> {
+    pub replacement: &'a str,
+    // 备份文件的后缀，例如 ".bak"
+    pub backup_suffix: Option<&'a str>,
+    // 只生成 diff，不修改文件
+    pub dry_run: bool,
+    // -a：含有 NUL 字节的文件也当作文本修改
+    pub text: bool,
+}
+
+// 已经把替换结果写进同目录临时文件、还没有覆盖原文件的一次修改
+// 没有 commit 就被丢弃时会删掉临时文件，原文件保持不变
+pub struct Staged {
+    path: PathBuf,
+    tmp: Option<PathBuf>,
+    backup_suffix: Option<String>,
+    // 被修改的行数
+    pub changed: usize,
+    // 只有 dry_run 时才生成
+    pub diff: Option<String>,
+}
+
+impl Staged {
+    // 备份原文件并用临时文件覆盖它；没有需要写的内容时什么都不做
+    pub fn commit(mut self) -> Result<(), MinigrepError> {
+        let Some(tmp) = self.tmp.take() else {
+            return Ok(());
+        };
+        let result = replace_with(&self.path, &tmp, self.backup_suffix.as_deref());
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        result.map_err(|err| MinigrepError::io(&self.path, err))
+    }
+}
+
+impl Drop for Staged {
+    fn drop(&mut self) {
+        if let Some(tmp) = &self.tmp {
+            let _ = fs::remove_file(tmp);
+        }
+    }
+}
+
+// 第一阶段：读文件、做替换，把结果写进临时文件
+// 多个文件时先把所有文件都 stage 好再逐个 commit，读写失败就不会留下只改了一部分的文件
+// 二进制文件和不是 UTF-8 的文件不能按行替换，打印警告后返回 None，不影响其它文件
+pub fn stage(
+    path: &Path,
+    matcher: &Matcher,
+    options: &EditOptions,
+) -> Result<Option<Staged>, MinigrepError> {
+    let bytes = fs::read(path).map_err(|err| MinigrepError::io(path, err))?;
+    if !options.text && stream::looks_binary(&bytes) {
+        eprintln!("minigrep: {}: binary file, not edited", path.display());
+        return Ok(None);
+    }
+    let Ok(contents) = String::from_utf8(bytes) else {
+        eprintln!("minigrep: {}: not valid UTF-8, not edited", path.display());
+        return Ok(None);
+    };
+    let mut edited = String::with_capacity(contents.len());
+    // (行号, 原行, 新行)
+    let mut changes = Vec::new();
+
+    for (i, raw) in contents.split_inclusive('\n').enumerate() {
+        // 换行符原样保留，只替换行的内容
+        let line = raw.trim_end_matches(['\n', '\r']);
+        let ending = &raw[line.len()..];
+        if matcher.is_match(line) {
+            let replaced = matcher.replace(line, options.replacement);
+            if replaced != line {
+                edited.push_str(&replaced);
+                edited.push_str(ending);
+                changes.push((i + 1, line, replaced));
+                continue;
+            }
+        }
+        edited.push_str(raw);
+    }
+
+    let mut staged = Staged {
+        path: path.to_path_buf(),
+        tmp: None,
+        backup_suffix: options.backup_suffix.map(String::from),
+        changed: changes.len(),
+        diff: None,
+    };
+    if changes.is_empty() {
+        return Ok(Some(staged));
+    }
+    if options.dry_run {
+        staged.diff = Some(unified_diff(path, &changes));
+        return Ok(Some(staged));
+    }
+
+    let tmp = temp_path(path);
+    if let Err(err) = write_temp(path, &tmp, &edited) {
+        let _ = fs::remove_file(&tmp);
+        return Err(MinigrepError::io(path, err));
+    }
+    staged.tmp = Some(tmp);
+    Ok(Some(staged))
+}
+
+fn write_temp(path: &Path, tmp: &Path, edited: &str) -> io::Result<()> {
+    let mut file = fs::File::create(tmp)?;
+    file.write_all(edited.as_bytes())?;
+    file.sync_all()?;
+    // 保留原文件的权限
+    fs::set_permissions(tmp, fs::metadata(path)?.permissions())
+}
+
+fn replace_with(path: &Path, tmp: &Path, backup: Option<&str>) -> io::Result<()> {
+    if let Some(suffix) = backup {
+        fs::copy(path, with_suffix(path, suffix))?;
+    }
+    fs::rename(tmp, path)
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+// 临时文件放在同一个目录下，保证 rename 是原子操作；
+// 所有文件同时处于 stage 状态，加上序号避免同一个文件出现两次时共用临时文件
+fn temp_path(path: &Path) -> PathBuf {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let id = NEXT.fetch_add(1, Ordering::Relaxed);
+    path.with_file_name(format!(".{name}.minigrep-{}-{id}.tmp", std::process::id()))
+}
+
+// 每一行的替换不会改变行数，所以相邻的修改合并成一个 hunk 即可，不需要通用的 diff 算法
+fn unified_diff(path: &Path, changes: &[(usize, &str, String)]) -> String {
+    // 绝对路径去掉开头的 /，否则会变成 a//abs/path
+    let shown = path.strip_prefix("/").unwrap_or(path);
+    let mut diff = format!("--- a/{0}\n+++ b/{0}\n", shown.display());
+    let mut i = 0;
+    while i < changes.len() {
+        let start = changes[i].0;
+        let mut end = i + 1;
+        while end < changes.len() && changes[end].0 == changes[end - 1].0 + 1 {
+            end += 1;
+        }
+        let hunk = &changes[i..end];
+        diff.push_str(&format!("@@ -{start},{0} +{start},{0} @@\n", hunk.len()));
+        for (_, old, _) in hunk {
+            diff.push_str(&format!("-{old}\n"));
+        }
+        for (_, _, new) in hunk {
+            diff.push_str(&format!("+{new}\n"));
+        }
+        i = end;
+    }
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewrites_with_backup() {
+        let dir = std::env::temp_dir().join(format!("minigrep-edit-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notes.txt");
+        fs::write(&path, "call foo()\r\nkeep\nfoo(foo)\n").unwrap();
+        let matcher = Matcher::literal("foo", false);
+
+        let preview = EditOptions {
+            replacement: "bar",
+            backup_suffix: None,
+            dry_run: true,
+            text: false,
+        };
+        let staged = stage(&path, &matcher, &preview).unwrap().unwrap();
+        assert_eq!(2, staged.changed);
+        assert!(staged.diff.as_ref().unwrap().ends_with(
+            "@@ -1,1 +1,1 @@\n-call foo()\n+call bar()\n@@ -3,1 +3,1 @@\n-foo(foo)\n+bar(bar)\n"
+        ));
+        assert_eq!(
+            "call foo()\r\nkeep\nfoo(foo)\n",
+            fs::read_to_string(&path).unwrap()
+        );
+
+        let write = EditOptions {
+            dry_run: false,
+            backup_suffix: Some(".orig"),
+            ..preview
+        };
+        staged.commit().unwrap();
+        stage(&path, &matcher, &write)
+            .unwrap()
+            .unwrap()
+            .commit()
+            .unwrap();
+        let edited = fs::read_to_string(&path).unwrap();
+        let backup = fs::read_to_string(dir.join("notes.txt.orig")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!("call bar()\r\nkeep\nbar(bar)\n", edited);
+        assert_eq!("call foo()\r\nkeep\nfoo(foo)\n", backup);
+    }
+
+    #[test]
+    fn absolute_path_diff_header() {
+        let changes = [(1, "foo", "bar".to_string())];
+        assert!(unified_diff(Path::new("/srv/notes.txt"), &changes)
+            .starts_with("--- a/srv/notes.txt\n+++ b/srv/notes.txt\n"));
+        assert!(unified_diff(Path::new("notes.txt"), &changes)
+            .starts_with("--- a/notes.txt\n+++ b/notes.txt\n"));
+    }
+
+    #[test]
+    fn dropped_stage_leaves_file_alone() {
+        let dir = std::env::temp_dir().join(format!("minigrep-stage-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notes.txt");
+        fs::write(&path, "foo\n").unwrap();
+        let options = EditOptions {
+            replacement: "bar",
+            backup_suffix: None,
+            dry_run: false,
+            text: false,
+        };
+
+        let staged = stage(&path, &Matcher::literal("foo", false), &options)
+            .unwrap()
+            .unwrap();
+        assert_eq!(1, staged.changed);
+        drop(staged);
+        let contents = fs::read_to_string(&path).unwrap();
+        let leftovers = fs::read_dir(&dir).unwrap().count();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!("foo\n", contents);
+        assert_eq!(1, leftovers);
+    }
+
+    #[test]
+    fn skips_binary_and_non_utf8() {
+        let dir = std::env::temp_dir().join(format!("minigrep-skip-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let binary = dir.join("data.bin");
+        let latin1 = dir.join("latin1.txt");
+        fs::write(&binary, b"foo\0\n").unwrap();
+        fs::write(&latin1, b"foo caf\xe9\n").unwrap();
+        let matcher = Matcher::literal("foo", false);
+        let options = EditOptions {
+            replacement: "bar",
+            backup_suffix: None,
+            dry_run: false,
+            text: false,
+        };
+
+        assert!(stage(&binary, &matcher, &options).unwrap().is_none());
+        assert!(stage(&latin1, &matcher, &options).unwrap().is_none());
+        let text = EditOptions {
+            text: true,
+            ..options
+        };
+        let staged = stage(&binary, &matcher, &text).unwrap().unwrap();
+        staged.commit().unwrap();
+        let edited = fs::read(&binary).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(b"bar\0\n", &edited[..]);
+    }
+}
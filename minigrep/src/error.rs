@@ -0,0 +1,120 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+// minigrep 所有可能的失败，库的调用方可以按种类分别处理
+#[derive(Debug)]
+pub enum MinigrepError {
+    // 命令行参数有误
+    Usage(String),
+    // 读取输入失败；path 为 None 时是标准输入
+    Io {
+        path: Option<PathBuf>,
+        source: io::Error,
+    },
+    // 写输出失败，通常是 stdout（例如管道另一端已经关闭）
+    Output(io::Error),
+    // 文件内容不是合法的 UTF-8（例如 --in-place 或 -f 读取的文件）
+    Decoding {
+        path: PathBuf,
+    },
+    // 正则、glob、布尔查询或者多模式自动机构建失败
+    Pattern(Box<dyn Error + Send + Sync>),
+}
+
+impl MinigrepError {
+    // 把读取 path 时的 io::Error 和路径绑在一起
+    pub fn io(path: impl Into<PathBuf>, source: io::Error) -> MinigrepError {
+        MinigrepError::Io {
+            path: Some(path.into()),
+            source,
+        }
+    }
+
+    // 输出的读取方已经退出（例如 minigrep ... | head），这时应该安静地结束
+    pub fn is_broken_pipe(&self) -> bool {
+        matches!(self, MinigrepError::Output(err) if err.kind() == io::ErrorKind::BrokenPipe)
+    }
+
+    // 和 grep 一致：0 表示有匹配，1 表示没有匹配，2 表示出错
+    pub fn exit_code(&self) -> i32 {
+        2
+    }
+}
+
+impl fmt::Display for MinigrepError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MinigrepError::Usage(message) => write!(f, "{message}"),
+            MinigrepError::Io {
+                path: Some(path),
+                source,
+            } => write!(f, "{}: {source}", path.display()),
+            MinigrepError::Io { path: None, source } => write!(f, "{source}"),
+            MinigrepError::Output(source) => write!(f, "write error: {source}"),
+            MinigrepError::Decoding { path } => {
+                write!(f, "{}: file is not valid UTF-8", path.display())
+            }
+            MinigrepError::Pattern(err) => write!(f, "invalid pattern: {err}"),
+        }
+    }
+}
+
+impl Error for MinigrepError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MinigrepError::Io { source, .. } | MinigrepError::Output(source) => Some(source),
+            MinigrepError::Pattern(err) => Some(err.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+// 没有路径信息的 I/O 错误来自写输出；读取输入的错误总是通过 MinigrepError::io 带上路径
+impl From<io::Error> for MinigrepError {
+    fn from(source: io::Error) -> MinigrepError {
+        MinigrepError::Output(source)
+    }
+}
+
+impl From<regex::Error> for MinigrepError {
+    fn from(err: regex::Error) -> MinigrepError {
+        MinigrepError::Pattern(Box::new(err))
+    }
+}
+
+impl From<crate::QueryError> for MinigrepError {
+    fn from(err: crate::QueryError) -> MinigrepError {
+        MinigrepError::Pattern(Box::new(err))
+    }
+}
+
+impl From<aho_corasick::BuildError> for MinigrepError {
+    fn from(err: aho_corasick::BuildError) -> MinigrepError {
+        MinigrepError::Pattern(Box::new(err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_io_errors() {
+        let err = MinigrepError::io("a.txt", io::Error::new(io::ErrorKind::NotFound, "missing"));
+        assert_eq!("a.txt: missing", err.to_string());
+        assert!(err.source().is_some());
+
+        // 解压失败之类的 InvalidData 仍然是读取错误，不是 UTF-8 解码错误
+        let err = MinigrepError::io("b.gz", io::Error::new(io::ErrorKind::InvalidData, "bad"));
+        assert_eq!("b.gz: bad", err.to_string());
+        assert_eq!(2, err.exit_code());
+
+        let err = MinigrepError::from(io::Error::from(io::ErrorKind::BrokenPipe));
+        assert!(err.is_broken_pipe());
+        assert!(
+            !MinigrepError::io("-", io::Error::from(io::ErrorKind::BrokenPipe)).is_broken_pipe()
+        );
+    }
+}
@@ -0,0 +1,252 @@
+use std::ops::Range;
+
+use crate::fold_table::{ASCII_FOLD_LEAD_BYTES, CASE_FOLDING};
+
+// 大小写折叠的方式，对应 Unicode CaseFolding.txt 中的 C+S、C+F 以及土耳其语的 T
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum CaseFolding {
+    // 一对一折叠：ß 不会变成 ss
+    Simple,
+    // 允许一个字符折叠成多个：ß 和 SS 相等
+    #[default]
+    Full,
+    // 在 Full 的基础上使用土耳其语规则：I ↔ ı，İ ↔ i
+    Turkic,
+}
+
+// 一个字符折叠后的结果，最多 3 个字符，放在栈上避免分配
+#[derive(Clone, Copy)]
+pub struct Folded {
+    chars: [char; 3],
+    len: u8,
+    pos: u8,
+}
+
+impl Folded {
+    fn one(c: char) -> Folded {
+        Folded {
+            chars: [c, '\0', '\0'],
+            len: 1,
+            pos: 0,
+        }
+    }
+
+    fn many(s: &[char]) -> Folded {
+        let mut chars = ['\0'; 3];
+        chars[..s.len()].copy_from_slice(s);
+        Folded {
+            chars,
+            len: s.len() as u8,
+            pos: 0,
+        }
+    }
+}
+
+impl Iterator for Folded {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        if self.pos == self.len {
+            return None;
+        }
+        self.pos += 1;
+        Some(self.chars[self.pos as usize - 1])
+    }
+}
+
+// 中日韩文字、假名、谚文等没有大小写，也没有别的字符会折叠成它们
+fn is_caseless(c: char) -> bool {
+    matches!(c, '\u{2e80}'..='\u{a63f}' | '\u{ac00}'..='\u{d7ff}')
+}
+
+pub fn fold_char(c: char, mode: CaseFolding) -> Folded {
+    // ASCII 是最常见的情况，先走快速路径
+    if c.is_ascii() {
+        return match (mode, c) {
+            (CaseFolding::Turkic, 'I') => Folded::one('ı'),
+            _ => Folded::one(c.to_ascii_lowercase()),
+        };
+    }
+    if is_caseless(c) {
+        return Folded::one(c);
+    }
+    if mode == CaseFolding::Turkic && c == 'İ' {
+        return Folded::one('i');
+    }
+    // 折叠规则来自 Unicode 的 CaseFolding.txt，见 fold_table.rs
+    let Ok(i) = CASE_FOLDING.binary_search_by_key(&c, |&(from, _, _)| from) else {
+        return Folded::one(c);
+    };
+    match CASE_FOLDING[i] {
+        (_, simple, []) => Folded::one(simple),
+        (_, simple, _) if mode == CaseFolding::Simple => Folded::one(simple),
+        (_, _, full) => Folded::many(full),
+    }
+}
+
+// 折叠整个 text，同时记下折叠结果里每个位置对应的原文位置
+// 返回的 Vec 长度是折叠结果的字节数加一：位于某个原文字符折叠结果中间的位置是 None
+pub fn fold_with_offsets(text: &str, mode: CaseFolding) -> (String, Vec<Option<usize>>) {
+    let mut folded = String::with_capacity(text.len());
+    let mut offsets = Vec::with_capacity(text.len() + 1);
+    for (i, c) in text.char_indices() {
+        offsets.push(Some(i));
+        folded.extend(fold_char(c, mode));
+        offsets.resize(folded.len(), None);
+    }
+    offsets.push(Some(text.len()));
+    (folded, offsets)
+}
+
+// 预先折叠好的查询串，匹配时逐字符折叠 haystack，不需要为每一行分配新的 String
+pub struct FoldedQuery {
+    chars: Vec<char>,
+    mode: CaseFolding,
+    // 折叠后的第一个字符是 ASCII 时记下来，查找时可以按字节快速跳过
+    first_ascii: Option<u8>,
+}
+
+impl FoldedQuery {
+    pub fn new(query: &str, mode: CaseFolding) -> FoldedQuery {
+        let chars: Vec<char> = query.chars().flat_map(|c| fold_char(c, mode)).collect();
+        let first_ascii = chars.first().filter(|c| c.is_ascii()).map(|&c| c as u8);
+        FoldedQuery {
+            chars,
+            mode,
+            first_ascii,
+        }
+    }
+
+    pub fn is_match(&self, line: &str) -> bool {
+        self.chars.is_empty() || self.find_at(line, 0).is_some()
+    }
+
+    // 所有互不重叠的匹配，范围落在原文的字符边界上
+    pub fn find_spans(&self, line: &str) -> Vec<Range<usize>> {
+        let mut spans = Vec::new();
+        if self.chars.is_empty() {
+            return spans;
+        }
+        let mut start = 0;
+        while let Some(span) = self.find_at(line, start) {
+            start = span.end;
+            spans.push(span);
+        }
+        spans
+    }
+
+    // 从 start 开始找第一个匹配
+    fn find_at(&self, line: &str, start: usize) -> Option<Range<usize>> {
+        if let Some(first) = self.first_ascii {
+            // 逐字节扫描：UTF-8 的后续字节（0x80..0xC0）不在候选里，所以 i 总是字符边界
+            let bytes = line.as_bytes();
+            return (start..bytes.len()).find_map(|i| {
+                let b = bytes[i];
+                let candidate = if b.is_ascii() {
+                    b.to_ascii_lowercase() == first
+                } else {
+                    // 以其它字节开头的字符不可能匹配 ASCII 开头的查询串
+                    ASCII_FOLD_LEAD_BYTES.contains(&b)
+                };
+                if !candidate {
+                    return None;
+                }
+                self.match_len(&line[i..]).map(|len| i..i + len)
+            });
+        }
+
+        let first = self.chars[0];
+        if is_caseless(first) {
+            // 只有这个字符本身能匹配，直接用标准库的子串查找定位候选位置
+            return line[start..].match_indices(first).find_map(|(i, _)| {
+                let begin = start + i;
+                self.match_len(&line[begin..]).map(|len| begin..begin + len)
+            });
+        }
+        line[start..].char_indices().find_map(|(i, c)| {
+            // 先只比较第一个折叠字符，大部分位置在这里就被排除了
+            if fold_char(c, self.mode).next() != Some(first) {
+                return None;
+            }
+            let begin = start + i;
+            self.match_len(&line[begin..]).map(|len| begin..begin + len)
+        })
+    }
+
+    // 如果 haystack 以查询串开头，返回在 haystack 中消耗的字节数
+    fn match_len(&self, haystack: &str) -> Option<usize> {
+        let mut needle = self.chars.iter();
+        let mut remaining = self.chars.len();
+        for (i, c) in haystack.char_indices() {
+            for folded in fold_char(c, self.mode) {
+                // 查询串在某个字符的折叠结果中途结束，不算完整匹配
+                if needle.next() != Some(&folded) {
+                    return None;
+                }
+                remaining -= 1;
+            }
+            if remaining == 0 {
+                return Some(i + c.len_utf8());
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_folding() {
+        let query = FoldedQuery::new("STRASSE", CaseFolding::Full);
+
+        assert_eq!(vec![0..7, 13..20], query.find_spans("Straße oder STRASSE"));
+        assert!(query.is_match("strasse"));
+        assert!(!FoldedQuery::new("STRASSE", CaseFolding::Simple).is_match("Straße"));
+        assert!(FoldedQuery::new("ΣΟΦΟΣ", CaseFolding::Simple).is_match("σοφος"));
+        assert!(FoldedQuery::new("σοφος", CaseFolding::Simple).is_match("ΣΟΦΟΣ"));
+    }
+
+    #[test]
+    fn full_folding_from_case_folding_txt() {
+        // 希腊字母的 ypogegrammeni：ᾈ 的简单折叠是 ᾀ，完整折叠是 ἀι
+        let query = FoldedQuery::new("ἀι", CaseFolding::Full);
+        assert!(query.is_match("ᾈ"));
+        assert!(query.is_match("ᾀ"));
+        assert!(FoldedQuery::new("ᾈ", CaseFolding::Simple).is_match("ᾀ"));
+        assert!(!FoldedQuery::new("ἀι", CaseFolding::Simple).is_match("ᾈ"));
+        assert!(FoldedQuery::new("ὐ", CaseFolding::Full).is_match("υ\u{313}"));
+
+        // 亚美尼亚文的连字：ﬓ 是 մ + ն
+        assert!(FoldedQuery::new("ՄՆ", CaseFolding::Full).is_match("ﬓ"));
+        assert!(FoldedQuery::new("ﬓ", CaseFolding::Full).is_match("մն"));
+        assert!(!FoldedQuery::new("ՄՆ", CaseFolding::Simple).is_match("ﬓ"));
+
+        // 查询以 ASCII 开头时，ß 也要作为候选位置
+        assert_eq!(
+            vec![2..4],
+            FoldedQuery::new("ss", CaseFolding::Full).find_spans("a ß")
+        );
+    }
+
+    #[test]
+    fn folds_text_with_offsets() {
+        let (folded, offsets) = fold_with_offsets("Aß", CaseFolding::Full);
+        assert_eq!("ass", folded);
+        assert_eq!(vec![Some(0), Some(1), None, Some(3)], offsets);
+    }
+
+    #[test]
+    fn turkic_dotted_i() {
+        let turkic = FoldedQuery::new("istanbul", CaseFolding::Turkic);
+        assert!(turkic.is_match("İSTANBUL"));
+        assert!(!turkic.is_match("ISTANBUL"));
+        assert!(FoldedQuery::new("ıspanak", CaseFolding::Turkic).is_match("ISPANAK"));
+
+        // 非土耳其语规则下，İ 折叠成 i + U+0307，单独的 i 不能匹配它的一半
+        let full = FoldedQuery::new("i", CaseFolding::Full);
+        assert!(!full.is_match("İ"));
+        assert!(FoldedQuery::new("i\u{307}", CaseFolding::Full).is_match("İ"));
+    }
+}
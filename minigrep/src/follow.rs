@@ -0,0 +1,585 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use encoding_rs::{Decoder, Encoding};
+
+use crate::output::{Line, Printer, PrinterSink, Stats};
+use crate::{stream, walk, Config, MinigrepError, Searcher, Sink};
+
+// 两次检查文件之间的间隔；用轮询而不是 inotify，这样在所有平台上行为一致
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+// 每次最多从文件读这么多字节，文件现有的内容再大也不会一次读进内存
+const CHUNK_SIZE: usize = 64 * 1024;
+
+// --follow：先搜索文件现有的内容，然后像 tail -f 一样持续读取追加的数据
+// 只有每个文件都读到 -m 的上限（或者遇到二进制内容）才会返回，否则一直运行到进程被中断
+pub fn follow(
+    files: &[PathBuf],
+    searcher: &Searcher,
+    printer: &Printer,
+    out: &mut impl Write,
+) -> Result<Stats, MinigrepError> {
+    let mut follower = Follower::new(files, searcher, printer)?;
+    follower.begin(out)?;
+    loop {
+        if follower.poll(out)? {
+            return Ok(follower.stats());
+        }
+        out.flush()?;
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+// 正在跟踪的一个文件
+struct Followed {
+    path: PathBuf,
+    file: File,
+    // 打开的文件的 (设备号, inode)，和路径当前指向的文件不同时说明日志被轮转了
+    id: Option<(u64, u64)>,
+    // 已经读到的位置
+    pos: u64,
+    // 文件开头还没读够、不能判断有没有 BOM 的字节
+    head: Vec<u8>,
+    // None 表示还没有确定编码；Some(None) 表示不转码，原样交给 scan
+    decoder: Option<Option<Decoder>>,
+    // 已经解码、还没有遇到换行符的半行数据
+    partial: Vec<u8>,
+    line_number: usize,
+    line_offset: usize,
+    // 之前的数据里出现过 NUL，后面的内容也按二进制处理
+    binary: bool,
+    // 这个文件选中的行数，-m 按文件计算
+    matched: usize,
+    // 达到 -m 上限或者提示过二进制文件有匹配之后不再读取
+    done: bool,
+    // 轮转后打开新文件失败，已经提示过，下次轮询时再试
+    reopen_failed: bool,
+    // 这个文件的统计，文件结束跟踪时放进 --json 的 end 事件
+    stats: Stats,
+}
+
+struct Follower<'a> {
+    files: Vec<Followed>,
+    searcher: &'a Searcher,
+    printer: &'a Printer,
+}
+
+impl<'a> Follower<'a> {
+    fn new(
+        paths: &[PathBuf],
+        searcher: &'a Searcher,
+        printer: &'a Printer,
+    ) -> Result<Follower<'a>, MinigrepError> {
+        let mut files = Vec::new();
+        for path in paths {
+            if path.is_dir() {
+                return Err(MinigrepError::Usage(format!(
+                    "--follow needs files, but '{}' is a directory",
+                    path.display()
+                )));
+            }
+            let file = File::open(path).map_err(|err| MinigrepError::io(path, err))?;
+            let id = file.metadata().ok().and_then(|m| file_id(&m));
+            files.push(Followed {
+                path: path.clone(),
+                file,
+                id,
+                pos: 0,
+                head: Vec::new(),
+                decoder: None,
+                partial: Vec::new(),
+                line_number: 0,
+                line_offset: 0,
+                binary: false,
+                matched: 0,
+                done: false,
+                reopen_failed: false,
+                stats: Stats {
+                    searches: 1,
+                    ..Stats::default()
+                },
+            });
+        }
+        Ok(Follower {
+            files,
+            searcher,
+            printer,
+        })
+    }
+
+    // 每个文件只有一个 begin 事件；scan 每搜索一批行都会调用 Sink::begin/finish，
+    // 所以 FollowSink 不转发它们，由这里和 search_lines 在文件开始和结束跟踪时输出
+    fn begin(&self, out: &mut impl Write) -> Result<(), MinigrepError> {
+        for followed in &self.files {
+            self.printer.begin(out, &followed.path)?;
+        }
+        Ok(())
+    }
+
+    // 所有文件的统计之和
+    fn stats(&self) -> Stats {
+        let mut total = Stats::default();
+        for followed in &self.files {
+            total.add(&followed.stats);
+        }
+        total
+    }
+
+    // 读取每个文件新增的内容并输出匹配的行；所有文件都不再需要读取时返回 true
+    fn poll(&mut self, out: &mut impl Write) -> Result<bool, MinigrepError> {
+        for i in 0..self.files.len() {
+            if self.files[i].done {
+                continue;
+            }
+            let path = self.files[i].path.clone();
+            // 路径暂时不存在（轮转到一半）时，继续读旧文件剩下的内容
+            let current = fs::metadata(&path).ok();
+            let rotated = current
+                .as_ref()
+                .is_some_and(|m| file_id(m).is_some() && file_id(m) != self.files[i].id);
+            if rotated {
+                // 旧文件在被换掉之前写进去的内容也要处理完
+                self.read_new(i, out)?;
+                if self.files[i].done {
+                    continue;
+                }
+                // 轮转程序可能在刚改名之后、新文件建好之前又换了一次：
+                // 打开失败时保留旧文件，下次轮询再试，只提示一次
+                let file = match File::open(&path) {
+                    Ok(file) => file,
+                    Err(err) => {
+                        if !self.files[i].reopen_failed {
+                            eprintln!(
+                                "minigrep: {}: file was replaced but can't be opened ({err}); retrying",
+                                path.display()
+                            );
+                            self.files[i].reopen_failed = true;
+                        }
+                        continue;
+                    }
+                };
+                eprintln!(
+                    "minigrep: {}: file was replaced; following the new file",
+                    path.display()
+                );
+                let followed = &mut self.files[i];
+                followed.reopen_failed = false;
+                followed.id = file.metadata().ok().and_then(|m| file_id(&m));
+                followed.file = file;
+                followed.restart();
+            } else if current.is_some_and(|m| m.len() < self.files[i].pos) {
+                eprintln!("minigrep: {}: file truncated", path.display());
+                self.files[i].restart();
+            }
+            self.read_new(i, out)?;
+        }
+        Ok(self.files.iter().all(|followed| followed.done))
+    }
+
+    // 从上次的位置开始按块读到文件末尾，每读一块就搜索其中完整的行
+    fn read_new(&mut self, i: usize, out: &mut impl Write) -> Result<(), MinigrepError> {
+        let encoding = self.searcher.options().encoding;
+        let followed = &mut self.files[i];
+        let path = followed.path.clone();
+        let error = |err| MinigrepError::io(&path, err);
+        followed
+            .file
+            .seek(SeekFrom::Start(followed.pos))
+            .map_err(error)?;
+        let mut chunk = vec![0; CHUNK_SIZE];
+        while !self.files[i].done {
+            let followed = &mut self.files[i];
+            let read = followed.file.read(&mut chunk).map_err(error)?;
+            if read == 0 {
+                break;
+            }
+            followed.pos += read as u64;
+            followed.stats.bytes_searched += read;
+            followed.decode(&chunk[..read], encoding);
+            self.search_lines(i, out)?;
+        }
+        Ok(())
+    }
+
+    // 把已经凑齐的完整行交给 Searcher，最后不带换行符的半行留到下次
+    fn search_lines(&mut self, i: usize, out: &mut impl Write) -> Result<(), MinigrepError> {
+        let followed = &mut self.files[i];
+        let Some(end) = memchr::memrchr(b'\n', &followed.partial) else {
+            return Ok(());
+        };
+        let lines: Vec<u8> = followed.partial.drain(..=end).collect();
+        let matched_before = followed.matched;
+        let mut sink = FollowSink {
+            inner: PrinterSink::new(self.printer, &followed.path, out),
+            line_number: followed.line_number,
+            line_offset: followed.line_offset,
+            matched: &mut followed.matched,
+            max_count: self.searcher.options().max_count,
+            stopped: false,
+        };
+        let stats = stream::search_lines(&lines[..], self.searcher, &mut sink, followed.binary)?;
+        followed.done = sink.stopped;
+        followed.binary |= !self.searcher.options().text && stream::looks_binary(&lines);
+        followed.line_number += memchr::memchr_iter(b'\n', &lines).count();
+        followed.line_offset += lines.len();
+
+        followed.stats.matched_lines += stats.matched_lines;
+        followed.stats.matches += stats.matches;
+        followed.stats.elapsed += stats.elapsed;
+        if matched_before == 0 && followed.matched > 0 {
+            followed.stats.searches_with_match = 1;
+        }
+        if followed.done {
+            self.printer.end(out, &followed.path, &followed.stats)?;
+        }
+        Ok(())
+    }
+}
+
+impl Followed {
+    // 文件被截断或者换成了新文件，从头开始读
+    fn restart(&mut self) {
+        self.pos = 0;
+        self.head.clear();
+        self.decoder = None;
+        self.partial.clear();
+        self.line_number = 0;
+        self.line_offset = 0;
+        self.binary = false;
+    }
+
+    // 和 Searcher 读文件时一样转码：BOM 优先，其次是 --encoding，都没有时原样保留
+    fn decode(&mut self, bytes: &[u8], encoding: Option<&'static Encoding>) {
+        if self.decoder.is_none() {
+            self.head.extend_from_slice(bytes);
+            if may_be_bom_prefix(&self.head) {
+                return;
+            }
+            let encoding = Encoding::for_bom(&self.head)
+                .map(|(encoding, _)| encoding)
+                .or(encoding);
+            self.decoder = Some(encoding.map(Encoding::new_decoder_with_bom_removal));
+            let head = std::mem::take(&mut self.head);
+            return self.decode(&head, encoding);
+        }
+        match self.decoder.as_mut().unwrap() {
+            None => self.partial.extend_from_slice(bytes),
+            Some(decoder) => {
+                let start = self.partial.len();
+                let max = decoder
+                    .max_utf8_buffer_length(bytes.len())
+                    .unwrap_or(bytes.len() * 3 + 16);
+                self.partial.resize(start + max, 0);
+                let (_, _, written, _) =
+                    decoder.decode_to_utf8(bytes, &mut self.partial[start..], false);
+                self.partial.truncate(start + written);
+            }
+        }
+    }
+}
+
+// 文件开头太短，还看不出是不是 BOM
+fn may_be_bom_prefix(head: &[u8]) -> bool {
+    [&b"\xef\xbb\xbf"[..], b"\xff\xfe", b"\xfe\xff"]
+        .iter()
+        .any(|bom| head.len() < bom.len() && bom.starts_with(head))
+}
+
+// 把每一批行的行号和偏移换算成在整个文件里的位置，并按文件计算 -m
+// begin/finish 不转发，见 Follower::begin
+struct FollowSink<'a, W> {
+    inner: PrinterSink<'a, W>,
+    line_number: usize,
+    line_offset: usize,
+    matched: &'a mut usize,
+    max_count: Option<usize>,
+    stopped: bool,
+}
+
+impl<W> FollowSink<'_, W> {
+    fn rebase<'l>(&self, line: &Line<'l>) -> Line<'l> {
+        Line {
+            number: self.line_number + line.number,
+            offset: self.line_offset + line.offset,
+            ..*line
+        }
+    }
+
+    fn keep_going(&mut self, more: bool) -> bool {
+        self.stopped = !more || self.max_count.is_some_and(|max| *self.matched >= max);
+        !self.stopped
+    }
+}
+
+impl<W: Write> Sink for FollowSink<'_, W> {
+    fn matched(&mut self, line: &Line) -> io::Result<bool> {
+        let more = self.inner.matched(&self.rebase(line))?;
+        *self.matched += 1;
+        Ok(self.keep_going(more))
+    }
+
+    fn binary_match(&mut self, line: &Line) -> io::Result<bool> {
+        let more = self.inner.binary_match(&self.rebase(line))?;
+        *self.matched += 1;
+        Ok(self.keep_going(more))
+    }
+}
+
+#[cfg(unix)]
+fn file_id(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+// 其他平台拿不到 inode，只能检测到截断
+#[cfg(not(unix))]
+fn file_id(_metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+// --watch DIR：搜索一遍，然后等到 DIR 下有文件新增、删除或者修改时再搜索一遍
+// search 出错时只打印错误，继续等待下一次变化
+pub fn watch(
+    config: &Config,
+    mut search: impl FnMut() -> Result<Stats, MinigrepError>,
+) -> Result<Stats, MinigrepError> {
+    let mut snapshot = Snapshot::take_or_report(config);
+    loop {
+        if let Err(err) = search() {
+            eprintln!("minigrep: {err}");
+        }
+        io::stdout().flush()?;
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let next = Snapshot::take_or_report(config);
+            let changed = snapshot.changed(&next);
+            snapshot = next;
+            if let Some(first) = changed.first() {
+                match changed.len() {
+                    1 => eprintln!("minigrep: {} changed, searching again", first.display()),
+                    n => eprintln!(
+                        "minigrep: {} and {} more changed, searching again",
+                        first.display(),
+                        n - 1
+                    ),
+                }
+                break;
+            }
+        }
+    }
+}
+
+// 被监视的目录下每个文件的大小和修改时间
+#[derive(PartialEq)]
+struct Snapshot(BTreeMap<PathBuf, (u64, Option<SystemTime>)>);
+
+impl Snapshot {
+    // 遍历失败（例如目录暂时被删掉）时打印错误，当作目录是空的，下次再试
+    fn take_or_report(config: &Config) -> Snapshot {
+        Snapshot::take(config).unwrap_or_else(|err| {
+            eprintln!("minigrep: {err}");
+            Snapshot(BTreeMap::new())
+        })
+    }
+
+    fn take(config: &Config) -> Result<Snapshot, MinigrepError> {
+        let (files, errors) =
+            walk::collect_files(&config.file_paths, &config.include, &config.exclude)?;
+        // 读不了的子目录只报告，其余文件照常监视
+        for err in errors {
+            eprintln!("minigrep: {err}");
+        }
+        Ok(Snapshot(
+            files
+                .into_iter()
+                .filter_map(|path| {
+                    // 遍历之后才被删除的文件当作不存在
+                    let metadata = fs::metadata(&path).ok()?;
+                    Some((path, (metadata.len(), metadata.modified().ok())))
+                })
+                .collect(),
+        ))
+    }
+
+    // 新增、删除或者修改过的文件，按路径排序
+    fn changed(&self, next: &Snapshot) -> Vec<PathBuf> {
+        let removed = self.0.keys().filter(|path| !next.0.contains_key(*path));
+        let mut changed: Vec<PathBuf> = next
+            .0
+            .iter()
+            .filter(|(path, state)| self.0.get(*path) != Some(state))
+            .map(|(path, _)| path)
+            .chain(removed)
+            .cloned()
+            .collect();
+        changed.sort();
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SearcherBuilder;
+
+    #[test]
+    fn follows_appends_truncation_and_rotation() {
+        let dir = std::env::temp_dir().join(format!("minigrep-follow-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("app.log");
+        fs::write(&log, "error one\ninfo\nerror tw").unwrap();
+
+        let config = Config {
+            line_number: true,
+            ..Config::default()
+        };
+        let searcher = SearcherBuilder::new().pattern("error").build().unwrap();
+        let printer = Printer::new(&config, false);
+        let mut follower = Follower::new(std::slice::from_ref(&log), &searcher, &printer).unwrap();
+        let mut out = Vec::new();
+
+        // 半行要等到换行符出现才输出
+        follower.poll(&mut out).unwrap();
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&log)
+            .unwrap()
+            .write_all(b"o\nok\n")
+            .unwrap();
+        follower.poll(&mut out).unwrap();
+        assert_eq!("1:error one\n3:error two\n", String::from_utf8_lossy(&out));
+
+        out.clear();
+        fs::write(&log, "error 3\n").unwrap();
+        follower.poll(&mut out).unwrap();
+        assert_eq!("1:error 3\n", String::from_utf8_lossy(&out));
+
+        // 轮转：旧文件改名，再创建一个同名的新文件
+        out.clear();
+        fs::rename(&log, dir.join("app.log.1")).unwrap();
+        fs::write(&log, "new error\n").unwrap();
+        follower.poll(&mut out).unwrap();
+        assert_eq!("1:new error\n", String::from_utf8_lossy(&out));
+        assert_eq!(4, follower.stats().matched_lines);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn max_count_per_file_and_encoding() {
+        let dir = std::env::temp_dir().join(format!("minigrep-follow-m-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let a = dir.join("a.log");
+        let b = dir.join("b.log");
+        fs::write(&a, "error a1\nerror a2\n").unwrap();
+        fs::write(&b, b"caf\xe9 error\n").unwrap();
+
+        let searcher = SearcherBuilder::new()
+            .pattern("error")
+            .max_count(Some(1))
+            .encoding(Some(encoding_rs::WINDOWS_1252))
+            .build()
+            .unwrap();
+        let printer = Printer::new(&Config::default(), true);
+        let mut follower = Follower::new(&[a.clone(), b.clone()], &searcher, &printer).unwrap();
+        let mut out = Vec::new();
+        let finished = follower.poll(&mut out).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(finished);
+        assert_eq!(
+            format!("{}:error a1\n{}:café error\n", a.display(), b.display()),
+            String::from_utf8_lossy(&out)
+        );
+        assert_eq!(2, follower.stats().searches_with_match);
+    }
+
+    #[test]
+    fn retries_when_new_file_cannot_be_opened() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("minigrep-follow-r-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("app.log");
+        fs::write(&log, "error old\n").unwrap();
+
+        let searcher = SearcherBuilder::new().pattern("error").build().unwrap();
+        let printer = Printer::new(&Config::default(), false);
+        let mut follower = Follower::new(std::slice::from_ref(&log), &searcher, &printer).unwrap();
+        let mut out = Vec::new();
+        follower.poll(&mut out).unwrap();
+
+        // 新文件已经出现但还打不开（root 不受权限限制，这时第一次轮询就会切换过去）
+        fs::rename(&log, dir.join("app.log.1")).unwrap();
+        fs::write(&log, "error new\n").unwrap();
+        fs::set_permissions(&log, fs::Permissions::from_mode(0o000)).unwrap();
+        let first = follower.poll(&mut out);
+        fs::set_permissions(&log, fs::Permissions::from_mode(0o644)).unwrap();
+        let second = follower.poll(&mut out);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(!first.unwrap());
+        assert!(!second.unwrap());
+        assert_eq!("error old\nerror new\n", String::from_utf8_lossy(&out));
+    }
+
+    #[test]
+    fn json_begin_and_end_per_file() {
+        let dir = std::env::temp_dir().join(format!("minigrep-follow-j-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("app.log");
+        fs::write(&log, "error one\nerror two\n").unwrap();
+
+        let config = Config {
+            output: crate::OutputMode::Json,
+            ..Config::default()
+        };
+        let searcher = SearcherBuilder::new()
+            .pattern("error")
+            .max_count(Some(1))
+            .build()
+            .unwrap();
+        let printer = Printer::new(&config, false);
+        let mut out = Vec::new();
+        let stats = follow(std::slice::from_ref(&log), &searcher, &printer, &mut out).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let types: Vec<String> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| line.split('"').nth(3).unwrap().to_string())
+            .collect();
+        assert_eq!(vec!["begin", "match", "end"], types);
+        assert_eq!(1, stats.matched_lines);
+    }
+
+    #[test]
+    fn snapshot_changes() {
+        let dir = std::env::temp_dir().join(format!("minigrep-watch-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        fs::write(dir.join("b.txt"), "b").unwrap();
+        let config = Config {
+            file_paths: vec![dir.to_string_lossy().into_owned()],
+            ..Config::default()
+        };
+
+        let before = Snapshot::take(&config).unwrap();
+        fs::write(dir.join("a.txt"), "aa").unwrap();
+        fs::remove_file(dir.join("b.txt")).unwrap();
+        fs::write(dir.join("c.txt"), "c").unwrap();
+        let after = Snapshot::take(&config).unwrap();
+
+        assert_eq!(
+            vec![dir.join("a.txt"), dir.join("b.txt"), dir.join("c.txt")],
+            before.changed(&after)
+        );
+        assert!(after.changed(&after).is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
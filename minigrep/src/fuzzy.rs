@@ -0,0 +1,185 @@
+use std::ops::Range;
+
+use crate::fold::{self, CaseFolding};
+
+// --fuzzy K：查找和 query 的编辑距离（Levenshtein）不超过 K 的子串
+// query 不超过 64 个字符时用 Myers 的位并行算法，每个字符只需要几次位运算；
+// 更长的 query 退回到逐列计算的动态规划
+pub struct FuzzyQuery {
+    chars: Vec<char>,
+    max_distance: usize,
+    ignore_case: bool,
+    // 每个字符在 query 中出现的位置（第 i 位表示 query[i]）；ASCII 表有 1KB，放在堆上
+    ascii_masks: Box<[u64; 128]>,
+    other_masks: Vec<(char, u64)>,
+}
+
+impl FuzzyQuery {
+    pub fn new(query: &str, max_distance: usize, ignore_case: bool) -> FuzzyQuery {
+        let chars: Vec<char> = query.chars().map(|c| fold(c, ignore_case)).collect();
+        let mut ascii_masks = Box::new([0; 128]);
+        let mut other_masks: Vec<(char, u64)> = Vec::new();
+        for (i, &c) in chars.iter().enumerate().take(64) {
+            let bit = 1 << i;
+            if c.is_ascii() {
+                ascii_masks[c as usize] |= bit;
+            } else if let Some((_, mask)) = other_masks.iter_mut().find(|(o, _)| *o == c) {
+                *mask |= bit;
+            } else {
+                other_masks.push((c, bit));
+            }
+        }
+        FuzzyQuery {
+            chars,
+            max_distance,
+            ignore_case,
+            ascii_masks,
+            other_masks,
+        }
+    }
+
+    // 行内最好的匹配的编辑距离，超过阈值时返回 None
+    pub fn distance(&self, line: &str) -> Option<usize> {
+        self.best_end(line).map(|(_, distance)| distance)
+    }
+
+    // 最好的匹配在行内的字节范围以及它的编辑距离
+    pub fn find(&self, line: &str) -> Option<(Range<usize>, usize)> {
+        let (end, distance) = self.best_end(line)?;
+        let chars: Vec<(usize, char)> = line.char_indices().collect();
+        let start = self.best_start(&chars[..end], distance);
+        let byte = |i: usize| chars.get(i).map_or(line.len(), |&(b, _)| b);
+        Some((byte(start)..byte(end), distance))
+    }
+
+    // 返回 (匹配结束的字符位置（不含）, 编辑距离)，距离相同时取最靠前的
+    fn best_end(&self, line: &str) -> Option<(usize, usize)> {
+        let m = self.chars.len();
+        if m == 0 {
+            return Some((0, 0));
+        }
+        let mut best: Option<(usize, usize)> = None;
+        let mut record = |end: usize, score: usize| {
+            if score <= self.max_distance && best.is_none_or(|(_, d)| score < d) {
+                best = Some((end, score));
+            }
+        };
+        if m > 64 {
+            // 编辑距离最多是 m，行首空匹配的距离也是 m
+            record(0, m);
+            let mut column: Vec<usize> = (0..=m).collect();
+            for (j, c) in line.chars().enumerate() {
+                let c = fold(c, self.ignore_case);
+                let mut diagonal = column[0];
+                for i in 1..=m {
+                    let cost = usize::from(self.chars[i - 1] != c);
+                    let value = (diagonal + cost).min(column[i] + 1).min(column[i - 1] + 1);
+                    diagonal = column[i];
+                    column[i] = value;
+                }
+                record(j + 1, column[m]);
+            }
+            return best;
+        }
+
+        // Myers (1999)：VP/VN 记录 DP 矩阵当前列相邻两行之间 +1/-1 的差值
+        let last = 1u64 << (m - 1);
+        let mut vp = u64::MAX;
+        let mut vn = 0u64;
+        let mut score = m;
+        record(0, score);
+        for (j, c) in line.chars().enumerate() {
+            let eq = self.mask(fold(c, self.ignore_case));
+            let xv = eq | vn;
+            let xh = ((eq & vp).wrapping_add(vp) ^ vp) | eq;
+            let ph = vn | !(xh | vp);
+            let mh = vp & xh;
+            if ph & last != 0 {
+                score += 1;
+            } else if mh & last != 0 {
+                score -= 1;
+            }
+            // 子串搜索：第 0 行恒为 0，所以移位后不需要补 1
+            let ph = ph << 1;
+            let mh = mh << 1;
+            vp = mh | !(xv | ph);
+            vn = ph & xv;
+            record(j + 1, score);
+        }
+        best
+    }
+
+    // 从匹配的结尾往回做一次 DP，找出达到同样距离的最短匹配的起点
+    fn best_start(&self, chars: &[(usize, char)], distance: usize) -> usize {
+        let m = self.chars.len();
+        let window = chars.len().min(m + self.max_distance);
+        let text: Vec<char> = chars[chars.len() - window..]
+            .iter()
+            .rev()
+            .map(|&(_, c)| fold(c, self.ignore_case))
+            .collect();
+        // row[j]：query 的后 i 个字符对齐到 text 的前 j 个字符（都是倒序）的最小代价
+        let mut row: Vec<usize> = (0..=window).collect();
+        for &q in self.chars.iter().rev() {
+            let mut diagonal = row[0];
+            row[0] += 1;
+            for j in 1..=window {
+                let cost = usize::from(text[j - 1] != q);
+                let value = (diagonal + cost).min(row[j] + 1).min(row[j - 1] + 1);
+                diagonal = row[j];
+                row[j] = value;
+            }
+        }
+        let len = row.iter().position(|&d| d <= distance).unwrap_or(window);
+        chars.len() - len
+    }
+
+    fn mask(&self, c: char) -> u64 {
+        if c.is_ascii() {
+            self.ascii_masks[c as usize]
+        } else {
+            self.other_masks
+                .iter()
+                .find(|(o, _)| *o == c)
+                .map_or(0, |&(_, mask)| mask)
+        }
+    }
+}
+
+// 编辑距离按字符计算，所以忽略大小写时只用一对一的简单折叠
+fn fold(c: char, ignore_case: bool) -> char {
+    if ignore_case {
+        fold::fold_char(c, CaseFolding::Simple).next().unwrap_or(c)
+    } else {
+        c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn best_distance_and_span() {
+        let query = FuzzyQuery::new("connection", 2, false);
+
+        assert_eq!(Some(0), query.distance("lost connection to db"));
+        assert_eq!(Some(1), query.distance("lost conection to db"));
+        assert_eq!(Some(2), query.distance("lost connetcion to db"));
+        assert_eq!(None, query.distance("lost link to db"));
+        assert_eq!(Some((5..14, 1)), query.find("lost conection to db"));
+        assert_eq!(Some((4..13, 1)), query.find("é: connectio!"));
+    }
+
+    #[test]
+    fn long_queries_and_case() {
+        let long = "a".repeat(70);
+        let query = FuzzyQuery::new(&long, 1, false);
+        assert_eq!(Some(1), query.distance(&"a".repeat(69)));
+        assert_eq!(None, query.distance(&"a".repeat(68)));
+
+        let query = FuzzyQuery::new("Größe", 1, true);
+        assert_eq!(Some(0), query.distance("die GRÖßE"));
+        assert_eq!(Some(1), query.distance("die GRÖSE"));
+    }
+}
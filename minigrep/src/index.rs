@@ -0,0 +1,342 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use encoding_rs::Encoding;
+
+use crate::fold::{self, CaseFolding};
+use crate::{walk, Config, MinigrepError};
+
+// 索引文件放在被索引目录的根下；以 . 开头，遍历目录时会被当作隐藏文件跳过
+pub const INDEX_FILE: &str = ".minigrep-index";
+
+const MAGIC: &[u8; 6] = b"MGIDX\x01";
+
+// 一个文件在索引里的记录：建索引时的大小和修改时间，以及排好序的 trigram 列表
+struct Entry {
+    size: u64,
+    mtime: u64,
+    trigrams: Vec<u32>,
+}
+
+// 一个目录的 trigram 索引，键是相对于根目录的路径
+pub struct Index {
+    files: HashMap<String, Entry>,
+}
+
+// index build 的结果，用来给用户一个简短的汇报
+#[derive(Debug, Default, PartialEq)]
+pub struct BuildStats {
+    pub files: usize,
+    pub updated: usize,
+    pub removed: usize,
+}
+
+// minigrep index build DIR：建立或增量更新 DIR 下的索引
+// 大小和修改时间都没变的文件直接沿用旧记录，不再读取内容
+pub fn build(dir: &Path) -> Result<BuildStats, MinigrepError> {
+    let index_path = dir.join(INDEX_FILE);
+    let mut old = match Index::load(&index_path) {
+        Ok(index) => index.files,
+        // 没有索引或者索引格式不对时从头建立
+        Err(err)
+            if matches!(
+                err.kind(),
+                io::ErrorKind::NotFound | io::ErrorKind::InvalidData
+            ) =>
+        {
+            HashMap::new()
+        }
+        Err(err) => return Err(MinigrepError::io(index_path, err)),
+    };
+
+    let mut stats = BuildStats::default();
+    let mut files = HashMap::new();
+    let (paths, errors) = walk::collect_files(&[dir.to_string_lossy().into_owned()], &[], &[])?;
+    // 读不了的子目录不进索引；--index 只用索引排除文件，没有收录的文件照常搜索
+    for err in errors {
+        eprintln!("minigrep: {err}");
+    }
+    for path in paths {
+        let Some(key) = relative_key(dir, &path) else {
+            continue;
+        };
+        let metadata = fs::metadata(&path).map_err(|err| MinigrepError::io(&path, err))?;
+        let (size, mtime) = (metadata.len(), mtime_of(&metadata));
+        let entry = match old.remove(&key) {
+            Some(entry) if entry.size == size && entry.mtime == mtime => entry,
+            _ => {
+                let bytes = fs::read(&path).map_err(|err| MinigrepError::io(&path, err))?;
+                stats.updated += 1;
+                Entry {
+                    size,
+                    mtime,
+                    trigrams: trigrams(&decode(&bytes)),
+                }
+            }
+        };
+        files.insert(key, entry);
+    }
+    stats.files = files.len();
+    stats.removed = old.len();
+
+    Index { files }
+        .save(&index_path)
+        .map_err(|err| MinigrepError::io(&index_path, err))?;
+    Ok(stats)
+}
+
+impl Index {
+    fn load(path: &Path) -> io::Result<Index> {
+        let mut reader = io::BufReader::new(fs::File::open(path)?);
+        let mut magic = [0; MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a minigrep index",
+            ));
+        }
+
+        let count = read_u32(&mut reader)?;
+        let mut files = HashMap::with_capacity(count as usize);
+        for _ in 0..count {
+            let mut key = vec![0; read_u32(&mut reader)? as usize];
+            reader.read_exact(&mut key)?;
+            let key = String::from_utf8(key)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            let size = read_u64(&mut reader)?;
+            let mtime = read_u64(&mut reader)?;
+            let trigrams = (0..read_u32(&mut reader)?)
+                .map(|_| read_u32(&mut reader))
+                .collect::<io::Result<_>>()?;
+            files.insert(
+                key,
+                Entry {
+                    size,
+                    mtime,
+                    trigrams,
+                },
+            );
+        }
+        Ok(Index { files })
+    }
+
+    // 和 --in-place 一样先写临时文件再 rename，中途失败不会破坏旧索引
+    fn save(&self, path: &Path) -> io::Result<()> {
+        let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+        let result = self.write_to(&tmp).and_then(|_| fs::rename(&tmp, path));
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        result
+    }
+
+    fn write_to(&self, path: &Path) -> io::Result<()> {
+        let mut out = io::BufWriter::new(fs::File::create(path)?);
+        out.write_all(MAGIC)?;
+        out.write_all(&(self.files.len() as u32).to_le_bytes())?;
+        // 按路径排序写出，同样的内容总是得到同样的索引文件
+        let mut keys: Vec<_> = self.files.keys().collect();
+        keys.sort();
+        for key in keys {
+            let entry = &self.files[key];
+            out.write_all(&(key.len() as u32).to_le_bytes())?;
+            out.write_all(key.as_bytes())?;
+            out.write_all(&entry.size.to_le_bytes())?;
+            out.write_all(&entry.mtime.to_le_bytes())?;
+            out.write_all(&(entry.trigrams.len() as u32).to_le_bytes())?;
+            for trigram in &entry.trigrams {
+                out.write_all(&trigram.to_le_bytes())?;
+            }
+        }
+        out.into_inner()?.sync_all()
+    }
+}
+
+// --index：用索引排除不可能匹配的文件，剩下的候选文件仍然交给正常的搜索确认
+pub struct Filter {
+    // (索引所在的目录, 索引)
+    indexes: Vec<(PathBuf, Index)>,
+    // 每个模式需要的 trigram；None 表示这次查询没法用索引缩小范围
+    patterns: Option<Vec<Vec<u32>>>,
+}
+
+impl Filter {
+    // 每个目录参数都必须在它自己或者某个上级目录里有索引
+    pub fn new(config: &Config, patterns: &[String]) -> Result<Filter, MinigrepError> {
+        let mut indexes = Vec::new();
+        for dir in config
+            .file_paths
+            .iter()
+            .map(Path::new)
+            .filter(|p| p.is_dir())
+        {
+            let root = dir
+                .ancestors()
+                .find(|root| root.join(INDEX_FILE).is_file())
+                .ok_or_else(|| {
+                    MinigrepError::Usage(format!(
+                        "no index found for '{}' (run 'minigrep index build {0}' first)",
+                        dir.display()
+                    ))
+                })?;
+            let index_path = root.join(INDEX_FILE);
+            let index =
+                Index::load(&index_path).map_err(|err| MinigrepError::io(index_path, err))?;
+            indexes.push((root.to_path_buf(), index));
+        }
+
+        // 索引里存的是 Full 折叠后的 trigram：区分大小写的匹配和 Full/Simple 折叠的匹配
+        // 折叠后一定还是子串，所以都能用；正则、--fuzzy、--bool、-z、-v、土耳其语折叠以及 --encoding 不能用
+        let unusable = config.regex
+            || config.fuzzy.is_some()
+            || config.boolean
+            || config.search_zip
+            || config.invert
+            || config.encoding.is_some()
+            || (config.ignore_case && config.case_folding == CaseFolding::Turkic);
+        let patterns = (!unusable).then(|| patterns.iter().map(|p| trigrams(p)).collect());
+        Ok(Filter { indexes, patterns })
+    }
+
+    // 文件不在索引里或者建索引之后被改过时，总是当作候选
+    pub fn may_match(&self, path: &Path) -> bool {
+        let Some(patterns) = &self.patterns else {
+            return true;
+        };
+        let Some(entry) = self.entry(path) else {
+            return true;
+        };
+        let fresh =
+            fs::metadata(path).is_ok_and(|m| m.len() == entry.size && mtime_of(&m) == entry.mtime);
+        if !fresh {
+            return true;
+        }
+        patterns.iter().any(|needed| {
+            needed
+                .iter()
+                .all(|t| entry.trigrams.binary_search(t).is_ok())
+        })
+    }
+
+    fn entry(&self, path: &Path) -> Option<&Entry> {
+        self.indexes.iter().find_map(|(root, index)| {
+            let key = relative_key(root, path)?;
+            index.files.get(&key)
+        })
+    }
+}
+
+// 文件内容折叠成小写后的所有 3 字节片段，去重并排序
+// 按 UTF-8 字节切分，所以非 ASCII 文本同样适用
+fn trigrams(text: &str) -> Vec<u32> {
+    let mut trigrams = Vec::new();
+    let mut window = 0u32;
+    let mut seen = 0;
+    let mut buf = [0; 4];
+    for c in text
+        .chars()
+        .flat_map(|c| fold::fold_char(c, CaseFolding::Full))
+    {
+        for &byte in c.encode_utf8(&mut buf).as_bytes() {
+            window = (window << 8 | byte as u32) & 0xff_ffff;
+            seen += 1;
+            if seen >= 3 {
+                trigrams.push(window);
+            }
+        }
+    }
+    trigrams.sort_unstable();
+    trigrams.dedup();
+    trigrams
+}
+
+// 和搜索时一样识别 UTF-16 的 BOM，其余内容按 UTF-8 处理
+fn decode(bytes: &[u8]) -> String {
+    match Encoding::for_bom(bytes) {
+        Some((encoding, bom_len)) => encoding.decode_without_bom_handling(&bytes[bom_len..]).0,
+        None => String::from_utf8_lossy(bytes),
+    }
+    .into_owned()
+}
+
+fn relative_key(root: &Path, path: &Path) -> Option<String> {
+    path.strip_prefix(root)
+        .ok()?
+        .to_str()
+        .map(|s| s.replace('\\', "/"))
+}
+
+// 修改时间精确到纳秒；拿不到时记为 0，下次建索引会重新读取
+fn mtime_of(metadata: &fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_nanos() as u64)
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(query: &str, dir: &Path) -> Config {
+        Config {
+            query: query.to_string(),
+            file_paths: vec![dir.to_string_lossy().into_owned()],
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn trigrams_are_case_folded() {
+        assert_eq!(trigrams("Straße"), trigrams("STRASSE"));
+        assert!(trigrams("ab").is_empty());
+        assert_eq!(
+            vec![u32::from_be_bytes([0, b'a', b'b', b'c'])],
+            trigrams("abc")
+        );
+    }
+
+    #[test]
+    fn narrows_candidates_and_updates_incrementally() {
+        let root = std::env::temp_dir().join(format!("minigrep-index-{}", std::process::id()));
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(root.join("a.txt"), "safe, fast, productive").unwrap();
+        fs::write(root.join("sub/b.txt"), "Pick three.").unwrap();
+
+        let stats = build(&root).unwrap();
+        assert_eq!((2, 2, 0), (stats.files, stats.updated, stats.removed));
+
+        let filter = Filter::new(&config("three", &root), &["three".to_string()]).unwrap();
+        assert!(!filter.may_match(&root.join("a.txt")));
+        assert!(filter.may_match(&root.join("sub/b.txt")));
+
+        // 子目录会使用上级目录里的索引；改过的文件即使还没重建索引也是候选
+        fs::write(root.join("a.txt"), "three, four").unwrap();
+        let filter =
+            Filter::new(&config("three", &root.join("sub")), &["three".to_string()]).unwrap();
+        assert!(filter.may_match(&root.join("a.txt")));
+
+        fs::remove_file(root.join("sub/b.txt")).unwrap();
+        let stats = build(&root).unwrap();
+        assert_eq!((1, 1, 1), (stats.files, stats.updated, stats.removed));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,68 @@
+// --json 输出用到的最小 JSON 编码，只需要字符串转义和 base64
+
+// 把任意字节编码成 JSON 对象：合法 UTF-8 用 {"text":...}，否则用 {"bytes":base64}
+pub fn data(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => format!("{{\"text\":{}}}", string(text)),
+        Err(_) => format!("{{\"bytes\":\"{}\"}}", base64(bytes)),
+    }
+}
+
+// 带引号并转义的 JSON 字符串
+pub fn string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            // 其它控制字符统一用 \uXXXX
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_strings() {
+        assert_eq!(r#""say \"hi\"\n\u0001""#, string("say \"hi\"\n\u{1}"));
+    }
+
+    #[test]
+    fn invalid_utf8_as_base64() {
+        assert_eq!(r#"{"text":"ok"}"#, data(b"ok"));
+        assert_eq!(r#"{"bytes":"/2Fi"}"#, data(b"\xffab"));
+        assert_eq!(r#"{"bytes":"/w=="}"#, data(b"\xff"));
+    }
+}
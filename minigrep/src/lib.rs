@@ -1,54 +1,620 @@
-use std::error::Error;
-use std::{env, fs};
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Instant;
 
+use encoding_rs::Encoding;
+use memchr::memmem;
+use regex::Regex;
+
+use output::PrinterSink;
+
+mod archive;
+mod cli;
+mod edit;
+mod error;
+mod fold;
+mod fold_table;
+mod follow;
+mod fuzzy;
+mod index;
+mod json;
+mod matcher;
+mod output;
+mod pool;
+mod query;
+mod searcher;
+mod stream;
+mod walk;
+
+pub use cli::{Command, USAGE};
+pub use error::MinigrepError;
+pub use fold::CaseFolding;
+pub use index::{build as build_index, BuildStats};
+pub use matcher::{Hit, Matcher};
+pub use output::{Line, Stats};
+pub use query::QueryError;
+pub use searcher::{
+    Decoded, Matches, OwnedMatch, PatternKind, ReaderMatches, Scope, Searcher, SearcherBuilder,
+    Sink,
+};
+pub use stream::SearchOptions;
+
+#[derive(Default)]
 pub struct Config {
+    // 单个查询串；使用 -e/-f 时为空，所有位置参数都是路径
     pub query: String,
-    pub file_path: String,
+    // -e 给出的模式，可以重复
+    pub patterns: Vec<String>,
+    // -f 给出的模式文件，每行一个模式
+    pub pattern_files: Vec<String>,
+    // --show-pattern 在每个匹配行前输出命中的模式
+    pub show_pattern: bool,
+    // --replace 的替换文本
+    pub replace: Option<String>,
+    // --in-place[=SUFFIX]：直接改写文件，SUFFIX 非空时保留备份
+    pub in_place: bool,
+    pub backup_suffix: Option<String>,
+    // --dry-run：配合 --in-place 只输出 diff
+    pub dry_run: bool,
+    // --encoding 指定的编码；None 表示根据 BOM 自动识别 UTF-8/UTF-16，其余按 UTF-8 处理
+    pub encoding: Option<&'static Encoding>,
+    // -a：不做二进制文件检测
+    pub text: bool,
+    // -z：解压 gzip/zstd/xz 并搜索 tar 里的每个文件
+    pub search_zip: bool,
+    // -U：匹配可以跨越多行
+    pub multiline: bool,
+    // --bool：QUERY 是 AND/OR/NOT/NEAR 组成的布尔查询
+    pub boolean: bool,
+    // --scope：按行还是按段落求值
+    pub scope: Scope,
+    // 可以是多个文件或目录，目录会被递归搜索；"-" 表示标准输入
+    pub file_paths: Vec<String>,
+    // --include/--exclude 传入的 glob，只作用于目录遍历
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    // --follow：像 tail -f 一样持续读取文件追加的内容
+    pub follow: bool,
+    // --watch DIR：DIR 下的文件变化时重新搜索；DIR 也是唯一的搜索路径
+    pub watch: Option<String>,
+    // --index：先用 index build 建立的 trigram 索引排除不可能匹配的文件
+    pub index: bool,
     pub ignore_case: bool,
+    // 忽略大小写时字面量匹配使用的折叠方式
+    pub case_folding: CaseFolding,
+    // 为 true 时把 query 当作正则表达式，默认仍然是字面量匹配
+    pub regex: bool,
+    // --fuzzy K：允许最多 K 处编辑的近似匹配
+    pub fuzzy: Option<usize>,
+    // -n 输出行号，-b 输出行首的字节偏移
+    pub line_number: bool,
+    pub byte_offset: bool,
+    // -B/-A/-C 指定的上下文行数
+    pub before_context: usize,
+    pub after_context: usize,
+    // -w 只匹配完整的单词
+    pub word: bool,
+    // -v 选出不匹配的行
+    pub invert: bool,
+    // -m 每个文件最多选出多少行
+    pub max_count: Option<usize>,
+    pub output: OutputMode,
+    // -j 指定的线程数，0 表示使用 CPU 核数
+    pub threads: usize,
+    pub sort: SortMode,
+    pub color: ColorChoice,
 }
 
-impl Config {
-    // 这里的 Result 可能包含一个 Config 实例，也可能包含一条错误信息 &static str
-    pub fn build(args: &[String]) -> Result<Config, &'static str> {
-        if args.len() < 3 {
-            return Err("not enough arguments");
+// --color 的取值：auto 时只有 stdout 是终端并且没有设置 NO_COLOR 才输出颜色
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ColorChoice {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn use_color(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+            }
         }
+    }
+}
 
-        let query = args[1].clone();
-        let file_path = args[2].clone();
-        // 用于检查是否有值，有就返回 true，没有则返回 false
-        let ignore_case = env::var("IGNORE_CASE").is_ok();
+// 多线程搜索时的输出顺序：按完成先后（默认）或者按路径排序（--sort path/distance）
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum SortMode {
+    #[default]
+    None,
+    Path,
+    // 按路径输出文件，文件内的匹配行按 --fuzzy 的编辑距离排序
+    Distance,
+}
 
-        Ok(Config {
-            query,
-            file_path,
-            ignore_case,
-        })
+// 每个文件的输出方式：打印行、只打印计数（-c）、只打印文件名（-l）、JSON Lines（--json）
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum OutputMode {
+    #[default]
+    Lines,
+    Count,
+    FilesWithMatches,
+    Json,
+}
+
+// 一次匹配的完整信息，line 仍然借用自原始内容
+#[derive(Debug, PartialEq)]
+pub struct Match<'a> {
+    // 行号从 1 开始
+    pub line_number: usize,
+    // 行首在整个内容中的字节偏移
+    pub byte_offset: usize,
+    pub line: &'a str,
+    // 行内每一处匹配的字节范围
+    pub spans: Vec<Range<usize>>,
+    // 命中的模式编号（-e/-f 给出多个模式时有意义），从小到大且不重复
+    pub patterns: Vec<usize>,
+}
+
+impl<'a> Match<'a> {
+    pub(crate) fn from_hits(
+        line_number: usize,
+        byte_offset: usize,
+        line: &'a str,
+        hits: Vec<Hit>,
+    ) -> Match<'a> {
+        let mut patterns: Vec<usize> = hits.iter().map(|hit| hit.pattern).collect();
+        patterns.sort_unstable();
+        patterns.dedup();
+        Match {
+            line_number,
+            byte_offset,
+            line,
+            spans: hits.into_iter().map(|hit| hit.range).collect(),
+            patterns,
+        }
+    }
+}
+
+impl Config {
+    // 解析命令行参数；--help/--version 不会产生 Config，这里当作错误返回
+    pub fn build(args: &[String]) -> Result<Config, MinigrepError> {
+        match Command::parse(args)? {
+            Command::Search(config) => Ok(*config),
+            _ => Err(MinigrepError::Usage(cli::USAGE.to_string())),
+        }
     }
 }
 
 // 将主体逻辑( 例如业务逻辑 )从 main 中分离出去，这样 main 函数就保留主流程调用
-// 我们的程序无需返回任何值，但是为了满足 Result<T,E> 的要求，因此使用了 Ok(()) 返回一个单元类型 ()
-pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let contents = fs::read_to_string(config.file_path)?;
+// 返回所有文件的汇总统计，main 根据 matched_lines 决定退出码（有匹配 0，没有匹配 1）
+pub fn run(config: Config) -> Result<Stats, MinigrepError> {
+    if config.watch.is_some() {
+        return follow::watch(&config, || search_all(&config, &mut io::stdout().lock()));
+    }
+    search_all(&config, &mut io::stdout().lock())
+}
+
+// 搜索一遍所有输入，结果写到 out；--watch 时每次有文件变化都会重新调用
+// 多个输入时某个文件读取失败只把错误打印到 stderr，继续搜索其余文件，并记在 Stats::errors 里
+fn search_all(config: &Config, out: &mut impl Write) -> Result<Stats, MinigrepError> {
+    let patterns = load_patterns(config)?;
+    let searcher = searcher_builder(config, patterns.clone()).build()?;
 
-    let results = if config.ignore_case {
-        search_case_insensitive(&config.query, &contents)
+    let (mut files, walk_errors) =
+        walk::collect_files(&config.file_paths, &config.include, &config.exclude)?;
+    // 遍历出错的目录和读取失败的文件一样处理：报告之后继续搜索其余文件，最后以 2 退出
+    for err in &walk_errors {
+        eprintln!("minigrep: {err}");
+    }
+    let walk_errors = walk_errors.len();
+    if config.index {
+        let filter = index::Filter::new(config, &patterns)?;
+        files.retain(|file| filter.may_match(file));
+    }
+    // 和 grep -r 一样：搜索多个文件或者目录时，在每一行前面加上文件路径
+    let with_path =
+        config.file_paths.len() > 1 || config.file_paths.iter().any(|p| Path::new(p).is_dir());
+
+    let mut printer = output::Printer::new(config, with_path).with_color(config.color.use_color());
+    if config.show_pattern {
+        printer = printer.with_patterns(Arc::new(patterns));
+    }
+    let has_context = printer.has_context();
+    if config.in_place {
+        let mut stats = edit_files(config, &files, searcher.matcher(), out)?;
+        stats.errors += walk_errors;
+        return Ok(stats);
+    }
+
+    let start = Instant::now();
+    if config.follow {
+        let mut stats = follow::follow(&files, &searcher, &printer, out)?;
+        stats.errors += walk_errors;
+        printer.summary(out, &stats, start.elapsed())?;
+        return Ok(stats);
+    }
+
+    // 只有一个输入（包括标准输入）时直接流式写到 stdout，不经过线程池和缓冲区
+    if let [file] = &files[..] {
+        let mut stats = search_file(file, &searcher, &printer, config.search_zip, out)?;
+        stats.errors += walk_errors;
+        printer.summary(out, &stats, start.elapsed())?;
+        return Ok(stats);
+    }
+
+    let threads = if config.threads > 0 {
+        config.threads
     } else {
-        search(&config.query, &contents)
+        thread::available_parallelism().map_or(1, |n| n.get())
+    };
+
+    // 每个文件是一个任务，worker 把输出写进缓冲区，再通过 channel 发回主线程
+    let searcher = Arc::new(searcher);
+    let (tx, rx) = mpsc::channel();
+    let pool = pool::ThreadPool::new(threads);
+    let search_zip = config.search_zip;
+    // 写输出失败（例如 minigrep ... | head 的管道被关闭）后置位：
+    // 不再排入新的文件，已经排队的任务也直接跳过，drop(pool) 不用等它们搜完
+    let cancel = Arc::new(AtomicBool::new(false));
+
+    let mut printed_any = false;
+    let mut total = Stats {
+        errors: walk_errors,
+        ..Stats::default()
+    };
+    let mut emit = |result: Result<(Vec<u8>, Stats), MinigrepError>| -> io::Result<()> {
+        // worker 只写缓冲区，这里的错误都是读取输入失败，和 grep 一样报告之后继续
+        let (buf, stats) = match result {
+            Ok(found) => found,
+            Err(err) => {
+                eprintln!("minigrep: {err}");
+                total.errors += 1;
+                return Ok(());
+            }
+        };
+        total.add(&stats);
+        if buf.is_empty() {
+            return Ok(());
+        }
+        if has_context && printed_any {
+            writeln!(out, "--")?;
+        }
+        printed_any = true;
+        out.write_all(&buf)
+            .inspect_err(|_| cancel.store(true, Ordering::Relaxed))
+    };
+
+    let streaming = config.sort == SortMode::None;
+    for file in files {
+        if cancel.load(Ordering::Relaxed) {
+            break;
+        }
+        let tx = tx.clone();
+        let searcher = Arc::clone(&searcher);
+        let printer = printer.clone();
+        let cancel = Arc::clone(&cancel);
+        pool.execute(move || {
+            if cancel.load(Ordering::Relaxed) {
+                return;
+            }
+            let mut out = Vec::new();
+            let result = search_file(&file, &searcher, &printer, search_zip, &mut out)
+                .map(|stats| (out, stats));
+            // 主线程出错提前返回后接收端会被丢弃，这里发送失败可以忽略
+            let _ = tx.send((file, result));
+        });
+        // 不排序时边排队边输出已经完成的结果，写失败就能及时停止排队
+        if streaming {
+            for (_, result) in rx.try_iter() {
+                emit(result)?;
+            }
+        }
+    }
+    // 只保留 worker 手里的发送端，全部任务结束后 rx 的迭代才会停止
+    drop(tx);
+
+    match config.sort {
+        SortMode::None => {
+            // 按完成的先后顺序流式输出
+            for (_, result) in rx {
+                emit(result)?;
+            }
+        }
+        SortMode::Path | SortMode::Distance => {
+            let mut results: Vec<_> = rx.iter().collect();
+            results.sort_by(|a, b| a.0.cmp(&b.0));
+            for (_, result) in results {
+                emit(result)?;
+            }
+        }
+    }
+
+    printer.summary(out, &total, start.elapsed())?;
+    Ok(total)
+}
+
+// --in-place：先把所有文件的替换结果写进临时文件，全部成功后再逐个覆盖原文件，
+// --dry-run 时只把 diff 打印到 stdout；返回的统计中 matched_lines 是被替换的行数
+fn edit_files(
+    config: &Config,
+    files: &[PathBuf],
+    matcher: &Matcher,
+    out: &mut impl Write,
+) -> Result<Stats, MinigrepError> {
+    let options = edit::EditOptions {
+        replacement: config.replace.as_deref().unwrap_or_default(),
+        backup_suffix: config.backup_suffix.as_deref(),
+        dry_run: config.dry_run,
+        text: config.text,
+    };
+    let mut stats = Stats::default();
+    // 任何一个文件读写失败时，已经 stage 的临时文件随 staged 一起被删掉，没有文件被修改；
+    // 被跳过的二进制文件和非 UTF-8 文件不算失败
+    let mut staged = Vec::new();
+    for file in files {
+        staged.extend(edit::stage(file, matcher, &options)?);
+    }
+    let mut rewritten = 0;
+    for mut pending in staged {
+        if let Some(diff) = pending.diff.take() {
+            out.write_all(diff.as_bytes())?;
+        }
+        stats.searches += 1;
+        let changed = pending.changed;
+        stats.matched_lines += changed;
+        if changed > 0 {
+            stats.searches_with_match += 1;
+        }
+        // rename 失败时前面的文件已经改好了，要在错误里告诉用户
+        if let Err(err) = pending.commit() {
+            return Err(match err {
+                MinigrepError::Io { path, source } if rewritten > 0 => MinigrepError::Io {
+                    path,
+                    source: io::Error::new(
+                        source.kind(),
+                        format!("{source} ({rewritten} earlier file(s) were already rewritten)"),
+                    ),
+                },
+                err => err,
+            });
+        }
+        if !config.dry_run && changed > 0 {
+            rewritten += 1;
+        }
+    }
+    Ok(stats)
+}
+
+// 读取必须是 UTF-8 的文本文件（-f 的模式文件）
+pub(crate) fn read_text(path: &Path) -> Result<String, MinigrepError> {
+    let bytes = fs::read(path).map_err(|err| MinigrepError::io(path, err))?;
+    String::from_utf8(bytes).map_err(|_| MinigrepError::Decoding {
+        path: path.to_path_buf(),
+    })
+}
+
+// 收集所有模式：没有 -e/-f 时就是位置参数里的 query
+fn load_patterns(config: &Config) -> Result<Vec<String>, MinigrepError> {
+    if config.patterns.is_empty() && config.pattern_files.is_empty() {
+        return Ok(vec![config.query.clone()]);
+    }
+    let mut patterns = config.patterns.clone();
+    for file in &config.pattern_files {
+        let contents = read_text(Path::new(file))?;
+        patterns.extend(contents.lines().map(String::from));
+    }
+    Ok(patterns)
+}
+
+// 搜索一个输入；-z 时先解压，tar 里的每个文件分别搜索并在输出中带上 "archive:inner/path"
+fn search_file(
+    file: &Path,
+    searcher: &Searcher,
+    printer: &output::Printer,
+    search_zip: bool,
+    out: &mut impl Write,
+) -> Result<Stats, MinigrepError> {
+    if !search_zip {
+        return searcher.search_path(file, &mut PrinterSink::new(printer, file, out));
+    }
+
+    let in_archive_printer = printer.clone().with_path(true);
+    let mut total = Stats::default();
+    let mut printed_any = false;
+    archive::for_each_entry(file, &mut |name, in_archive, reader| {
+        if !in_archive {
+            let mut sink = PrinterSink::new(printer, name, &mut *out);
+            let stats = searcher.search_input(name, reader, &mut sink)?;
+            total.add(&stats);
+            return Ok(());
+        }
+        // 同一个归档里的多个文件之间和多个文件一样用 "--" 分隔上下文
+        let mut buf = Vec::new();
+        let stats = searcher.search_input(
+            name,
+            reader,
+            &mut PrinterSink::new(&in_archive_printer, name, &mut buf),
+        )?;
+        total.add(&stats);
+        if !buf.is_empty() {
+            if printed_any && printer.has_context() {
+                writeln!(out, "--")?;
+            }
+            printed_any = true;
+            out.write_all(&buf)?;
+        }
+        Ok(())
+    })?;
+    Ok(total)
+}
+
+// 命令行选项对应的 SearcherBuilder；-c/-l 不输出行，不需要收集上下文
+fn searcher_builder(config: &Config, patterns: Vec<String>) -> SearcherBuilder {
+    let kind = match config.fuzzy {
+        Some(max) => PatternKind::Fuzzy(max),
+        None if config.boolean => PatternKind::Boolean,
+        None if config.regex => PatternKind::Regex,
+        None => PatternKind::Literal,
     };
+    let context = matches!(config.output, OutputMode::Lines | OutputMode::Json);
+    SearcherBuilder::new()
+        .patterns(patterns)
+        .kind(kind)
+        .ignore_case(config.ignore_case)
+        .case_folding(config.case_folding)
+        .word(config.word)
+        .invert(config.invert)
+        .before_context(if context { config.before_context } else { 0 })
+        .after_context(if context { config.after_context } else { 0 })
+        .max_count(config.max_count)
+        .multiline(config.multiline)
+        .scope(config.scope)
+        .replace(config.replace.clone())
+        .text(config.text)
+        .encoding(config.encoding)
+}
+
+// 不再逐行调用 contains：用 memchr 的 SIMD 子串查找扫描整个 contents，
+// 找到匹配之后才去确定它所在行的边界，然后直接跳到下一行继续查找
+// 结果和按 lines() 分行之后逐行 contains 完全一致
+pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    // 包含换行符的 query 不可能出现在某一行里
+    if query.contains('\n') {
+        return Vec::new();
+    }
+    let finder = memmem::Finder::new(query);
+    let bytes = contents.as_bytes();
+    let mut results = Vec::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let Some(found) = finder.find(&bytes[pos..]) else {
+            break;
+        };
+        let hit = pos + found;
+        let start = memchr::memrchr(b'\n', &bytes[..hit]).map_or(0, |i| i + 1);
+        let end = memchr::memchr(b'\n', &bytes[hit..]).map_or(bytes.len(), |i| hit + i);
+        let line = &contents[start..end];
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        // 和 lines() 一样不把行尾的 \r 算进行内容；只有 query 以 \r 结尾时才会落到这里
+        if hit + query.len() > start + line.len() {
+            pos = hit + 1;
+            continue;
+        }
+        results.push(line);
+        pos = end + 1;
+    }
+    results
+}
 
-    for line in results {
-        println!("{line}");
+pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    // 只在开始时折叠一次 query，之后逐字符比较，不再为每一行调用 to_lowercase 分配 String
+    let query = fold::FoldedQuery::new(query, CaseFolding::Full);
+    let mut results = Vec::new();
+    for line in contents.lines() {
+        if query.is_match(line) {
+            results.push(line);
+        }
     }
+    results
+}
+
+// 早期只支持正则时的入口，现在和 search_with 是同一件事
+#[deprecated(note = "use search_with with Matcher::regex or Matcher::from(Regex)")]
+pub fn search_regex<'a>(re: &Regex, contents: &'a str) -> Vec<&'a str> {
+    search_with(&Matcher::from(re.clone()), contents)
+}
+
+// 通用入口：具体用字面量还是正则匹配由 Matcher 决定
+pub fn search_with<'a>(matcher: &Matcher, contents: &'a str) -> Vec<&'a str> {
+    contents
+        .lines()
+        .filter(|line| matcher.is_match(line))
+        .collect()
+}
+
+// 返回每一处匹配的行号、字节偏移和行内范围，而不只是行本身
+pub fn search_matches<'a>(matcher: &Matcher, contents: &'a str) -> Vec<Match<'a>> {
+    lines_with_offsets(contents)
+        .enumerate()
+        .filter(|(_, (_, line))| matcher.is_match(line))
+        .map(|(i, (byte_offset, line))| {
+            Match::from_hits(i + 1, byte_offset, line, matcher.highlights(line))
+        })
+        .collect()
+}
 
-    Ok(())
+// -v 的结果：所有不匹配的行，spans 为空
+pub fn search_inverted<'a>(matcher: &Matcher, contents: &'a str) -> Vec<Match<'a>> {
+    lines_with_offsets(contents)
+        .enumerate()
+        .filter(|(_, (_, line))| !matcher.is_match(line))
+        .map(|(i, (byte_offset, line))| Match {
+            line_number: i + 1,
+            byte_offset,
+            line,
+            spans: Vec::new(),
+            patterns: Vec::new(),
+        })
+        .collect()
+}
+
+// 与 str::lines 的切分规则一致（去掉 \n 或 \r\n），同时带上每行的起始偏移
+pub(crate) fn lines_with_offsets(contents: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut offset = 0;
+    contents.split_inclusive('\n').map(move |raw| {
+        let start = offset;
+        offset += raw.len();
+        let line = raw.strip_suffix('\n').unwrap_or(raw);
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        (start, line)
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn spans(m: &Match) -> Vec<(usize, usize)> {
+        m.spans.iter().map(|r| (r.start, r.end)).collect()
+    }
+
+    // 按命令行参数搜索写好内容的临时文件，返回输出；输出里的临时文件路径换成 FILE
+    fn cli_output(args: &[&str], contents: &[u8]) -> String {
+        // 测试是并行运行的，每次调用用不同的文件
+        static NEXT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let id = NEXT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let path = env::temp_dir().join(format!("minigrep-cli-{}-{id}.txt", std::process::id()));
+        fs::write(&path, contents).unwrap();
+        let args: Vec<String> = ["minigrep"]
+            .iter()
+            .chain(args)
+            .map(|s| s.to_string())
+            .chain([path.display().to_string()])
+            .collect();
+        let config = Config::build(&args).unwrap();
+        let searcher = searcher_builder(&config, load_patterns(&config).unwrap())
+            .build()
+            .unwrap();
+        let printer = output::Printer::new(&config, false);
+        let mut out = Vec::new();
+        search_file(&path, &searcher, &printer, false, &mut out).unwrap();
+        fs::remove_file(&path).unwrap();
+        String::from_utf8(out)
+            .unwrap()
+            .replace(&path.display().to_string(), "FILE")
+    }
+
     #[test]
     fn case_sensitive() {
         let query = "duct";
@@ -61,6 +627,15 @@ Duct tape.";
         assert_eq!(vec!["safe, fast, productive."], search(query, contents));
     }
 
+    #[test]
+    fn search_matches_line_by_line_contains() {
+        let contents = "duct\r\n\nproduct duct\nend\r\nduc";
+        for query in ["duct", "uc", "", "\r", "end\r", "t\nend", "duc"] {
+            let expected: Vec<&str> = contents.lines().filter(|l| l.contains(query)).collect();
+            assert_eq!(expected, search(query, contents), "{query:?}");
+        }
+    }
+
     #[test]
     fn case_insensitive() {
         let query = "rUsT";
@@ -75,27 +650,252 @@ Trust me.";
             search_case_insensitive(query, contents)
         );
     }
-}
 
-pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    let mut results = Vec::new();
-    // lines 方法将目标字符串进行按行分割
-    for line in contents.lines() {
-        if line.contains(query) {
-            results.push(line);
-        }
+    #[test]
+    fn case_insensitive_full_folding() {
+        let query = "STRASSE";
+        let contents = "\
+Hauptstraße 1
+Bahnhofstrasse 2
+Marktplatz 3";
+
+        assert_eq!(
+            vec!["Hauptstraße 1", "Bahnhofstrasse 2"],
+            search_case_insensitive(query, contents)
+        );
     }
-    results
-}
 
-pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    // 将 line 转换成全小写的字符串，返回的是 String
-    let query = query.to_lowercase();
-    let mut results = Vec::new();
-    for line in contents.lines() {
-        if line.to_lowercase().contains(&query) {
-            results.push(line);
+    #[test]
+    fn regex_pattern() {
+        let matcher = Matcher::regex(r"^\w+:$", false).unwrap();
+        let contents = "\
+Rust:
+safe, fast, productive.
+Pick three:
+Duct tape.";
+
+        assert_eq!(vec!["Rust:"], search_with(&matcher, contents));
+        #[allow(deprecated)]
+        let old = search_regex(&Regex::new(r"^\w+:$").unwrap(), contents);
+        assert_eq!(vec!["Rust:"], old);
+    }
+
+    #[test]
+    fn regex_flag() {
+        let args: Vec<String> = ["minigrep", "-E", r"\d{3}", "poem.txt"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let config = Config::build(&args).unwrap();
+
+        assert!(config.regex);
+        assert_eq!(r"\d{3}", config.query);
+        assert_eq!(vec!["poem.txt"], config.file_paths);
+    }
+
+    #[test]
+    fn many_paths_and_globs() {
+        let args: Vec<String> = [
+            "minigrep",
+            "fn",
+            "src",
+            "--include",
+            "*.rs",
+            "--exclude",
+            "target/",
+            "poem.txt",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        let config = Config::build(&args).unwrap();
+
+        assert_eq!(vec!["src", "poem.txt"], config.file_paths);
+        assert_eq!(vec!["*.rs"], config.include);
+        assert_eq!(vec!["target/"], config.exclude);
+    }
+
+    #[test]
+    fn invert_and_word() {
+        let matcher = Matcher::literal("rust", true).word(true);
+        let contents = "\
+Rust:
+Trust me.
+safe, fast, productive.";
+
+        let lines: Vec<&str> = search_inverted(&matcher, contents)
+            .iter()
+            .map(|m| m.line)
+            .collect();
+        assert_eq!(vec!["Trust me.", "safe, fast, productive."], lines);
+    }
+
+    #[test]
+    fn match_positions() {
+        let matcher = Matcher::literal("you", true);
+        let contents = "\
+I'm nobody! Who are you?
+Are YOU nobody, too?
+How dreary to be somebody!";
+
+        let matches = search_matches(&matcher, contents);
+
+        assert_eq!(2, matches.len());
+        assert_eq!(
+            (1, 0, "I'm nobody! Who are you?"),
+            (
+                matches[0].line_number,
+                matches[0].byte_offset,
+                matches[0].line
+            )
+        );
+        assert_eq!(vec![(20, 23)], spans(&matches[0]));
+        assert_eq!(
+            (2, 25, "Are YOU nobody, too?"),
+            (
+                matches[1].line_number,
+                matches[1].byte_offset,
+                matches[1].line
+            )
+        );
+        assert_eq!(vec![(4, 7)], spans(&matches[1]));
+    }
+
+    #[test]
+    fn json_keeps_latin1_bytes() {
+        let out = cli_output(&["--json", "caf"], b"caf\xe9 au lait\nthe\n");
+        let events: Vec<&str> = out.lines().collect();
+
+        assert_eq!(
+            r#"{"type":"match","data":{"path":{"text":"FILE"},"lines":{"bytes":"Y2Fm6SBhdSBsYWl0"},"line_number":1,"absolute_offset":0,"submatches":[{"match":{"text":"caf"},"start":0,"end":3,"pattern":0}]}}"#,
+            events[1]
+        );
+    }
+
+    #[test]
+    fn keeps_searching_after_missing_file() {
+        let dir = env::temp_dir().join(format!("minigrep-missing-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.txt"), "needle one\n").unwrap();
+        fs::write(dir.join("c.txt"), "hay\nneedle two\n").unwrap();
+        let path = |name: &str| dir.join(name).display().to_string();
+        let args = [
+            "minigrep".to_string(),
+            "--sort=path".to_string(),
+            "needle".to_string(),
+            path("a.txt"),
+            path("b.txt"),
+            path("c.txt"),
+        ];
+        let config = Config::build(&args).unwrap();
+        let mut out = Vec::new();
+        let stats = search_all(&config, &mut out).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            format!(
+                "{}:needle one\n{}:needle two\n",
+                path("a.txt"),
+                path("c.txt")
+            ),
+            String::from_utf8(out).unwrap()
+        );
+        assert_eq!(2, stats.matched_lines);
+        assert_eq!(1, stats.errors);
+    }
+
+    #[test]
+    fn stops_after_write_error() {
+        // 模拟 minigrep ... | head：读取方已经退出，每次写都失败
+        struct Closed;
+        impl Write for Closed {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::ErrorKind::BrokenPipe.into())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let dir = env::temp_dir().join(format!("minigrep-closed-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for i in 0..64 {
+            fs::write(dir.join(format!("{i}.txt")), "needle\n").unwrap();
         }
+        let args = [
+            "minigrep".to_string(),
+            "-j2".to_string(),
+            "needle".to_string(),
+            dir.display().to_string(),
+        ];
+        let config = Config::build(&args).unwrap();
+        let result = search_all(&config, &mut Closed);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(result.is_err_and(|err| err.is_broken_pipe()));
+    }
+
+    #[test]
+    fn keeps_searching_after_walk_error() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = env::temp_dir().join(format!("minigrep-walk-error-{}", std::process::id()));
+        let locked = dir.join("locked");
+        fs::create_dir_all(&locked).unwrap();
+        fs::write(locked.join("hidden.txt"), "needle hidden\n").unwrap();
+        fs::write(dir.join("open.txt"), "needle open\n").unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+        // root 不受权限限制，这时目录照样能读，也就不会有错误
+        let denied = fs::read_dir(&locked).is_err();
+
+        let args = [
+            "minigrep".to_string(),
+            "needle".to_string(),
+            dir.display().to_string(),
+        ];
+        let config = Config::build(&args).unwrap();
+        let mut out = Vec::new();
+        let stats = search_all(&config, &mut out);
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        let stats = stats.unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains(&format!("{}:needle open\n", dir.join("open.txt").display())));
+        assert_eq!(usize::from(denied), stats.errors);
+        assert_eq!(if denied { 1 } else { 2 }, stats.matched_lines);
+    }
+
+    #[test]
+    fn decodes_utf16_with_bom() {
+        let text = "first line\ncafé au lait\n";
+        let le: Vec<u8> = [0xff, 0xfe]
+            .into_iter()
+            .chain(text.encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+        let be: Vec<u8> = [0xfe, 0xff]
+            .into_iter()
+            .chain(text.encode_utf16().flat_map(u16::to_be_bytes))
+            .collect();
+
+        assert_eq!("2:café au lait\n", cli_output(&["-n", "café"], &le));
+        assert_eq!("2:café au lait\n", cli_output(&["-n", "café"], &be));
+        // 偏移是转码之后的 UTF-8 文本里的位置，不是文件里的（BOM 加上 11 个 UTF-16 码元是 24）
+        assert_eq!("11:café au lait\n", cli_output(&["-b", "café"], &le));
+    }
+
+    #[test]
+    fn encoding_option_changes_matches() {
+        let contents = b"caf\xe9 au lait\nna\xefve\n";
+
+        assert_eq!("", cli_output(&["café"], contents));
+        assert_eq!(
+            "café au lait\n",
+            cli_output(&["--encoding", "latin1", "café"], contents)
+        );
+        assert_eq!(
+            "naïve\n",
+            cli_output(&["--encoding=latin1", "-i", "NAÏVE"], contents)
+        );
     }
-    results
 }
@@ -1,4 +1,4 @@
-use minigrep::Config;
+use minigrep::Command;
 use std::env;
 use std::process;
 
@@ -8,21 +8,53 @@ fn main() {
     let args: Vec<String> = env::args().collect();
     // unwrap_or_else 是定义在 Result<T,E> 上的常用方法，如果 Result 是 Ok，那该方法就类似 unwrap：返回 Ok 内部的值
     // 如果是 Err，就调用闭包中的自定义代码对错误进行进一步处理
-    let config = Config::build(&args).unwrap_or_else(|err| {
+    let command = Command::parse(&args).unwrap_or_else(|err| {
         // 将错误信息重定向到 stderr 很简单
-        eprint!("Problem parsing arguments: {err}");
-        // 当 Result 包含错误时，我们不再调用 panic 让程序崩溃，而是通过 process::exit(1) 来终结进程
-        process::exit(1);
-        // 不太懂怎么在报错
+        eprintln!("minigrep: {err}");
+        // 当 Result 包含错误时，我们不再调用 panic 让程序崩溃，而是通过 process::exit 来终结进程
+        // 和 grep 一样，出错时退出码是 2
+        process::exit(err.exit_code());
     });
+    let config = match command {
+        Command::Search(config) => *config,
+        Command::IndexBuild(dir) => match minigrep::build_index(&dir) {
+            Ok(stats) => {
+                eprintln!(
+                    "minigrep: indexed {} files in {} ({} updated, {} removed)",
+                    stats.files,
+                    dir.display(),
+                    stats.updated,
+                    stats.removed
+                );
+                return;
+            }
+            Err(e) => {
+                eprintln!("minigrep: {e}");
+                process::exit(e.exit_code());
+            }
+        },
+        Command::Help => {
+            print!("{}", minigrep::USAGE);
+            return;
+        }
+        Command::Version => {
+            println!("minigrep {}", env!("CARGO_PKG_VERSION"));
+            return;
+        }
+    };
     // dbg! 宏来输出读取到的数组内容
     // dbg!(args);
-    eprint!("Searching for {}", config.query);
-    eprint!("In file {}", config.file_path);
-    // run(config);
-    if let Err(e) = minigrep::run(config) {
-        eprint!("Application error: {e}");
-        process::exit(1);
+    // 退出码：0 表示有匹配，1 表示没有匹配，2 表示出错（有文件读取失败时即使有匹配也是 2）
+    match minigrep::run(config) {
+        Ok(stats) if stats.errors > 0 => process::exit(2),
+        Ok(stats) if stats.matched_lines > 0 => process::exit(0),
+        Ok(_) => process::exit(1),
+        // 管道的读取方提前退出（例如 | head），不是错误
+        Err(e) if e.is_broken_pipe() => process::exit(0),
+        Err(e) => {
+            eprintln!("minigrep: {e}");
+            process::exit(e.exit_code());
+        }
     }
 }
 // cargo run -- to poem.txt > output.txt
@@ -0,0 +1,397 @@
+use std::ops::Range;
+
+use aho_corasick::{AhoCorasick, MatchKind};
+use memchr::memmem::Finder;
+use regex::{Regex, RegexBuilder};
+
+use crate::fold::{self, CaseFolding, FoldedQuery};
+use crate::fuzzy::FuzzyQuery;
+use crate::query::{Query, QueryError};
+
+// 匹配器：字面量匹配是默认模式，只有显式传入 -E/--regex 时才会编译正则
+pub struct Matcher {
+    kind: Kind,
+    // -w：匹配的前后都不能是单词字符
+    word: bool,
+}
+
+enum Kind {
+    // 区分大小写的字面量用 memchr 的 SIMD 子串查找；Finder 有将近 300 字节，放在堆上
+    Literal(Box<Finder<'static>>),
+    // 忽略大小写的字面量匹配，query 已经提前折叠好
+    Folded(FoldedQuery),
+    Regex(Regex),
+    // -e/-f 给出的多个字面量，用 Aho-Corasick 自动机一遍扫描
+    Multi(AhoCorasick),
+    // 忽略大小写的多个字面量：自动机里是折叠后的模式，匹配前先折叠整行
+    MultiFolded {
+        ac: AhoCorasick,
+        folding: CaseFolding,
+    },
+    // -E 加上多个模式：每个模式放进一个命名分组 p0、p1……，用来分辨是哪个模式命中
+    RegexMulti {
+        re: Regex,
+        groups: Vec<String>,
+    },
+    // --fuzzy：编辑距离不超过阈值的近似匹配，每行只报告最好的一处
+    Fuzzy(FuzzyQuery),
+    // --bool：AND/OR/NOT/NEAR 组合起来的查询，-w 已经作用在每个词上
+    Query(Query),
+}
+
+impl Matcher {
+    // 忽略大小写时使用完整的 Unicode case folding（ß 和 SS 相等）
+    pub fn literal(query: &str, ignore_case: bool) -> Matcher {
+        if ignore_case {
+            Matcher::folded(query, CaseFolding::Full)
+        } else {
+            Matcher {
+                kind: Kind::Literal(Box::new(Finder::new(query).into_owned())),
+                word: false,
+            }
+        }
+    }
+
+    // 用指定的折叠方式做忽略大小写的字面量匹配
+    pub fn folded(query: &str, folding: CaseFolding) -> Matcher {
+        Matcher {
+            kind: Kind::Folded(FoldedQuery::new(query, folding)),
+            word: false,
+        }
+    }
+
+    // 正则语法错误会通过 regex::Error 返回给调用方
+    pub fn regex(pattern: &str, ignore_case: bool) -> Result<Matcher, regex::Error> {
+        let re = RegexBuilder::new(pattern)
+            .case_insensitive(ignore_case)
+            .build()?;
+        Ok(Matcher {
+            kind: Kind::Regex(re),
+            word: false,
+        })
+    }
+
+    // 多个字面量模式；忽略大小写时和单个字面量一样使用完整的 Unicode case folding
+    pub fn multi(
+        patterns: &[String],
+        ignore_case: bool,
+    ) -> Result<Matcher, aho_corasick::BuildError> {
+        if ignore_case {
+            return Matcher::multi_folded(patterns, CaseFolding::Full);
+        }
+        let ac = AhoCorasick::builder()
+            .match_kind(MatchKind::LeftmostLongest)
+            .build(patterns)?;
+        Ok(Matcher {
+            kind: Kind::Multi(ac),
+            word: false,
+        })
+    }
+
+    // 用指定的折叠方式做忽略大小写的多模式匹配
+    pub fn multi_folded(
+        patterns: &[String],
+        folding: CaseFolding,
+    ) -> Result<Matcher, aho_corasick::BuildError> {
+        let folded = patterns
+            .iter()
+            .map(|pattern| fold::fold_with_offsets(pattern, folding).0);
+        let ac = AhoCorasick::builder()
+            .match_kind(MatchKind::LeftmostLongest)
+            .build(folded)?;
+        Ok(Matcher {
+            kind: Kind::MultiFolded { ac, folding },
+            word: false,
+        })
+    }
+
+    // 模式列表为空（比如 -f /dev/null）时什么都不匹配，和字面量的多模式一致
+    pub fn regex_multi(patterns: &[String], ignore_case: bool) -> Result<Matcher, regex::Error> {
+        if patterns.is_empty() {
+            return Ok(Matcher {
+                kind: Kind::Regex(Regex::new(r"[^\s\S]")?),
+                word: false,
+            });
+        }
+        let groups: Vec<String> = (0..patterns.len()).map(|i| format!("p{i}")).collect();
+        let alternation: Vec<String> = patterns
+            .iter()
+            .zip(&groups)
+            .map(|(pattern, name)| format!("(?P<{name}>{pattern})"))
+            .collect();
+        let re = RegexBuilder::new(&alternation.join("|"))
+            .case_insensitive(ignore_case)
+            .build()?;
+        Ok(Matcher {
+            kind: Kind::RegexMulti { re, groups },
+            word: false,
+        })
+    }
+
+    pub fn fuzzy(query: &str, max_distance: usize, ignore_case: bool) -> Matcher {
+        Matcher {
+            kind: Kind::Fuzzy(FuzzyQuery::new(query, max_distance, ignore_case)),
+            word: false,
+        }
+    }
+
+    // 布尔查询；词按字面量匹配，大小写和整词的规则和普通字面量一样
+    pub fn query(
+        query: &str,
+        ignore_case: bool,
+        folding: CaseFolding,
+        word: bool,
+    ) -> Result<Matcher, QueryError> {
+        Ok(Matcher {
+            kind: Kind::Query(Query::parse(query, ignore_case, folding, word)?),
+            word: false,
+        })
+    }
+
+    pub fn word(mut self, word: bool) -> Matcher {
+        self.word = word;
+        self
+    }
+
+    pub fn is_match(&self, line: &str) -> bool {
+        if self.word {
+            return !self.find_spans(line).is_empty();
+        }
+        match &self.kind {
+            Kind::Literal(finder) => finder.find(line.as_bytes()).is_some(),
+            Kind::Folded(query) => query.is_match(line),
+            Kind::Regex(re) | Kind::RegexMulti { re, .. } => re.is_match(line),
+            Kind::Multi(ac) => ac.is_match(line),
+            Kind::MultiFolded { .. } => !self.find_hits(line).is_empty(),
+            Kind::Fuzzy(query) => query.distance(line).is_some(),
+            Kind::Query(query) => query.is_match(line),
+        }
+    }
+
+    // --fuzzy 时返回行内最好的匹配的编辑距离，其他匹配方式总是 None
+    pub fn distance(&self, line: &str) -> Option<usize> {
+        match &self.kind {
+            Kind::Fuzzy(query) if self.word => query
+                .find(line)
+                .filter(|(range, _)| is_word_bounded(line, range))
+                .map(|(_, distance)| distance),
+            Kind::Fuzzy(query) => query.distance(line),
+            _ => None,
+        }
+    }
+
+    // 返回 line 中每一处（互不重叠的）匹配在行内的字节范围
+    pub fn find_spans(&self, line: &str) -> Vec<Range<usize>> {
+        self.find_hits(line)
+            .into_iter()
+            .map(|hit| hit.range)
+            .collect()
+    }
+
+    // 和 find_spans 一样，但同时给出命中的是第几个模式（单模式时总是 0）
+    pub fn find_hits(&self, line: &str) -> Vec<Hit> {
+        let hits: Vec<Hit> = match &self.kind {
+            // 空 query 在每个字节处都匹配，只保留字符边界上的，和 str::match_indices 一致
+            Kind::Literal(finder) => finder
+                .find_iter(line.as_bytes())
+                .filter(|&start| line.is_char_boundary(start))
+                .map(|start| Hit::first(start..start + finder.needle().len()))
+                .collect(),
+            Kind::Folded(query) => query.find_spans(line).into_iter().map(Hit::first).collect(),
+            Kind::Regex(re) => re.find_iter(line).map(|m| Hit::first(m.range())).collect(),
+            Kind::Multi(ac) => ac
+                .find_iter(line)
+                .map(|m| Hit {
+                    range: m.range(),
+                    pattern: m.pattern().as_usize(),
+                })
+                .collect(),
+            Kind::MultiFolded { ac, folding } => {
+                // 折叠后的命中可能落在某个字符折叠结果的中间（ß 折叠成 ss 后只命中一个 s），
+                // 这样的命中在原文里没有对应的范围，直接丢掉
+                let (folded, offsets) = fold::fold_with_offsets(line, *folding);
+                ac.find_iter(&folded)
+                    .filter_map(|m| {
+                        let start = offsets[m.start()]?;
+                        let end = offsets[m.end()]?;
+                        Some(Hit {
+                            range: start..end,
+                            pattern: m.pattern().as_usize(),
+                        })
+                    })
+                    .collect()
+            }
+            Kind::RegexMulti { re, groups } => re
+                .captures_iter(line)
+                .map(|caps| Hit {
+                    range: caps.get(0).unwrap().range(),
+                    pattern: groups
+                        .iter()
+                        .position(|name| caps.name(name).is_some())
+                        .unwrap_or(0),
+                })
+                .collect(),
+            Kind::Fuzzy(query) => query
+                .find(line)
+                .map(|(range, _)| Hit::first(range))
+                .into_iter()
+                .collect(),
+            Kind::Query(query) => query.find_hits(line),
+        };
+        if self.word {
+            hits.into_iter()
+                .filter(|hit| is_word_bounded(line, &hit.range))
+                .collect()
+        } else {
+            hits
+        }
+    }
+}
+
+impl Matcher {
+    // 要高亮的位置：布尔查询不要求 line 本身满足查询（--scope paragraph 判断的是整个段落），
+    // 其他匹配方式和 find_hits 一样
+    pub(crate) fn highlights(&self, line: &str) -> Vec<Hit> {
+        match &self.kind {
+            Kind::Query(query) => query.term_hits(line),
+            _ => self.find_hits(line),
+        }
+    }
+
+    // 被选中的行一定包含的字面量，stream 用它在整块缓冲区上预先过滤；
+    // 只有区分大小写的单个字面量才有（-w 只会让匹配更少）。包含 U+FFFD 的字面量可能匹配
+    // 不合法字节替换之后的内容，在原始字节里找不到，不能用来过滤
+    pub(crate) fn required_literal(&self) -> Option<&Finder<'static>> {
+        match &self.kind {
+            Kind::Literal(finder)
+                if !finder.needle().is_empty()
+                    && memchr::memmem::find(finder.needle(), "\u{fffd}".as_bytes()).is_none() =>
+            {
+                Some(finder)
+            }
+            _ => None,
+        }
+    }
+
+    // 把 line 中每一处匹配替换成 replacement；正则模式下可以用 $1、${name} 引用分组
+    pub fn replace(&self, line: &str, replacement: &str) -> String {
+        let mut out = String::with_capacity(line.len());
+        let mut last = 0;
+        for hit in self.find_hits(line) {
+            out.push_str(&line[last..hit.range.start]);
+            match &self.kind {
+                Kind::Regex(re) | Kind::RegexMulti { re, .. } => {
+                    if let Some(caps) = re.captures_at(line, hit.range.start) {
+                        caps.expand(replacement, &mut out);
+                    }
+                }
+                _ => out.push_str(replacement),
+            }
+            last = hit.range.end;
+        }
+        out.push_str(&line[last..]);
+        out
+    }
+}
+
+// 已经编译好的正则直接作为单模式的 Matcher
+impl From<Regex> for Matcher {
+    fn from(re: Regex) -> Matcher {
+        Matcher {
+            kind: Kind::Regex(re),
+            word: false,
+        }
+    }
+}
+
+// 一处匹配：行内的字节范围和命中的模式编号
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hit {
+    pub range: Range<usize>,
+    pub pattern: usize,
+}
+
+impl Hit {
+    fn first(range: Range<usize>) -> Hit {
+        Hit { range, pattern: 0 }
+    }
+}
+
+pub(crate) fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn is_word_bounded(line: &str, span: &Range<usize>) -> bool {
+    let before = line[..span.start].chars().next_back();
+    let after = line[span.end..].chars().next();
+    !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patterns(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn multi_pattern_hits() {
+        let matcher = Matcher::multi(&patterns(&["timeout", "retry", "debug"]), true).unwrap();
+        let hits = matcher.find_hits("RETRY after timeout");
+
+        assert_eq!(
+            vec![(0..5, 1), (12..19, 0)],
+            hits.into_iter()
+                .map(|h| (h.range, h.pattern))
+                .collect::<Vec<_>>()
+        );
+        assert!(!matcher.is_match("all good"));
+    }
+
+    #[test]
+    fn replace_with_groups() {
+        let matcher = Matcher::regex(r"(\w+)@(\w+)", false).unwrap();
+        assert_eq!(
+            "mail bob at example, alice at test",
+            matcher.replace("mail bob@example, alice@test", "$1 at $2")
+        );
+
+        let matcher = Matcher::literal("you", true).word(true);
+        assert_eq!(
+            "Who are we? your",
+            matcher.replace("Who are YOU? your", "we")
+        );
+    }
+
+    #[test]
+    fn regex_multi_hits() {
+        let matcher = Matcher::regex_multi(&patterns(&[r"\d+", r"[a-z]+"]), false).unwrap();
+        let ids: Vec<usize> = matcher
+            .find_hits("abc 123")
+            .iter()
+            .map(|h| h.pattern)
+            .collect();
+
+        assert_eq!(vec![1, 0], ids);
+    }
+
+    #[test]
+    fn multi_pattern_full_folding() {
+        let matcher = Matcher::multi(&patterns(&["straße", "foo"]), true).unwrap();
+        assert_eq!(vec![0..7, 12..15], matcher.find_spans("STRASSE and FOO"));
+        let matcher = Matcher::multi(&patterns(&["ss"]), true).unwrap();
+        assert_eq!(vec![1..3], matcher.find_spans("aßb"));
+        assert!(!Matcher::multi(&patterns(&["s"]), true)
+            .unwrap()
+            .is_match("ß"));
+    }
+
+    #[test]
+    fn empty_pattern_list_matches_nothing() {
+        let matcher = Matcher::regex_multi(&[], false).unwrap();
+        assert!(!matcher.is_match("anything"));
+        assert!(!matcher.is_match(""));
+        assert!(!Matcher::multi(&[], true).unwrap().is_match("anything"));
+    }
+}
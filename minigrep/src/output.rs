@@ -0,0 +1,503 @@
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::matcher::Hit;
+use crate::{json, Config, OutputMode, Sink, SortMode};
+
+// 交给 Sink 的一行：匹配行或者上下文行
+pub struct Line<'a> {
+    pub number: usize,
+    // 行首的字节偏移
+    pub offset: usize,
+    pub text: &'a str,
+    // 行的原始字节（不含换行符）；text 是按 UTF-8 解码后的内容，不合法的字节换成了 U+FFFD
+    // --json 用它原样输出不合法的字节；内容合法或者经过替换、转码时和 text 相同
+    pub bytes: &'a [u8],
+    // 行内的每一处匹配，上下文行和 -v 选出的行为空
+    pub hits: &'a [Hit],
+    pub is_match: bool,
+    // --fuzzy 时匹配行的编辑距离
+    pub distance: Option<usize>,
+}
+
+// 单个文件（或所有文件汇总）的搜索统计，用于 --json 的 end/summary 事件
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Stats {
+    pub searches: usize,
+    pub searches_with_match: usize,
+    pub matched_lines: usize,
+    pub matches: usize,
+    pub bytes_searched: usize,
+    pub elapsed: Duration,
+    // 读取失败（错误已经打印到 stderr）的输入个数
+    pub errors: usize,
+}
+
+impl Stats {
+    pub fn add(&mut self, other: &Stats) {
+        self.searches += other.searches;
+        self.searches_with_match += other.searches_with_match;
+        self.matched_lines += other.matched_lines;
+        self.matches += other.matches;
+        self.bytes_searched += other.bytes_searched;
+        self.elapsed += other.elapsed;
+        self.errors += other.errors;
+    }
+
+    fn to_json(self) -> String {
+        format!(
+            "{{\"elapsed\":{},\"searches\":{},\"searches_with_match\":{},\"bytes_searched\":{},\"matched_lines\":{},\"matches\":{}}}",
+            elapsed_json(self.elapsed),
+            self.searches,
+            self.searches_with_match,
+            self.bytes_searched,
+            self.matched_lines,
+            self.matches
+        )
+    }
+}
+
+// 负责按照 grep 的格式输出每一行
+// 匹配行用 ':' 分隔前缀，上下文行用 '-'，不相邻的片段之间输出 "--"
+// 输出目标是任意 Write，worker 线程可以先写进缓冲区，再交给主线程按顺序输出
+#[derive(Clone)]
+pub struct Printer {
+    mode: OutputMode,
+    with_path: bool,
+    line_number: bool,
+    byte_offset: bool,
+    before: usize,
+    after: usize,
+    // 是否输出 ANSI 颜色，由 run 根据 --color 和终端情况决定
+    color: bool,
+    // --show-pattern 时保存所有模式，用来在每行前输出命中的模式
+    patterns: Option<Arc<Vec<String>>>,
+    // --sort distance：同一个文件里的匹配行按编辑距离从小到大输出
+    rank: bool,
+}
+
+// 文件名、行号、分隔符和匹配内容分别使用的颜色（和 GNU grep 的默认配色一致）
+const PATH_COLOR: &str = "\x1b[35m";
+const NUMBER_COLOR: &str = "\x1b[32m";
+const SEP_COLOR: &str = "\x1b[36m";
+const MATCH_COLOR: &str = "\x1b[1;31m";
+const RESET: &str = "\x1b[0m";
+
+impl Printer {
+    pub fn new(config: &Config, with_path: bool) -> Printer {
+        Printer {
+            mode: config.output,
+            with_path,
+            line_number: config.line_number,
+            byte_offset: config.byte_offset,
+            before: config.before_context,
+            after: config.after_context,
+            color: false,
+            patterns: None,
+            rank: config.sort == SortMode::Distance,
+        }
+    }
+
+    pub fn with_patterns(mut self, patterns: Arc<Vec<String>>) -> Printer {
+        self.patterns = Some(patterns);
+        self
+    }
+
+    // 是否在每行前输出路径；归档里的文件总是需要
+    pub fn with_path(mut self, with_path: bool) -> Printer {
+        self.with_path = with_path;
+        self
+    }
+
+    pub fn with_color(mut self, color: bool) -> Printer {
+        self.color = color;
+        self
+    }
+
+    // 是否需要逐行输出（而不是 -c/-l 那样只输出汇总）
+    pub fn prints_lines(&self) -> bool {
+        matches!(self.mode, OutputMode::Lines | OutputMode::Json)
+    }
+
+    // 是否输出上下文；此时不同文件的片段之间也要用 "--" 分隔
+    pub fn has_context(&self) -> bool {
+        self.mode == OutputMode::Lines && (self.before > 0 || self.after > 0)
+    }
+
+    // 开始搜索一个文件，--json 时输出 begin 事件
+    pub fn begin(&self, out: &mut impl Write, path: &Path) -> io::Result<()> {
+        if self.mode == OutputMode::Json {
+            writeln!(
+                out,
+                "{{\"type\":\"begin\",\"data\":{{\"path\":{}}}}}",
+                path_json(path)
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn write_line(&self, out: &mut impl Write, path: &Path, line: &Line) -> io::Result<()> {
+        if self.mode == OutputMode::Json {
+            return self.write_json_line(out, path, line);
+        }
+        let sep = if line.is_match { ':' } else { '-' };
+        if self.with_path {
+            self.write_field(out, PATH_COLOR, &display_path(path), sep)?;
+        }
+        if self.line_number {
+            self.write_field(out, NUMBER_COLOR, &line.number.to_string(), sep)?;
+        }
+        if self.byte_offset {
+            self.write_field(out, NUMBER_COLOR, &line.offset.to_string(), sep)?;
+        }
+        if let Some(distance) = line.distance {
+            self.write_field(out, NUMBER_COLOR, &distance.to_string(), sep)?;
+        }
+        if let Some(patterns) = &self.patterns {
+            if line.is_match {
+                let mut ids: Vec<usize> = line.hits.iter().map(|hit| hit.pattern).collect();
+                ids.sort_unstable();
+                ids.dedup();
+                let names: Vec<&str> = ids.iter().map(|&id| patterns[id].as_str()).collect();
+                self.write_field(out, NUMBER_COLOR, &names.join(","), sep)?;
+            }
+        }
+        if !self.color || line.hits.is_empty() {
+            return writeln!(out, "{}", line.text);
+        }
+
+        // 逐段输出，匹配的部分包上颜色
+        let mut last = 0;
+        for hit in line.hits {
+            write!(
+                out,
+                "{}{MATCH_COLOR}{}{RESET}",
+                &line.text[last..hit.range.start],
+                &line.text[hit.range.clone()]
+            )?;
+            last = hit.range.end;
+        }
+        writeln!(out, "{}", &line.text[last..])
+    }
+
+    fn write_field(
+        &self,
+        out: &mut impl Write,
+        color: &str,
+        value: &str,
+        sep: char,
+    ) -> io::Result<()> {
+        if self.color {
+            write!(out, "{color}{value}{RESET}{SEP_COLOR}{sep}{RESET}")
+        } else {
+            write!(out, "{value}{sep}")
+        }
+    }
+
+    fn write_json_line(&self, out: &mut impl Write, path: &Path, line: &Line) -> io::Result<()> {
+        let kind = if line.is_match { "match" } else { "context" };
+        // 匹配位置是在解码后的 text 上算的，输出时换算回原始字节里的位置
+        let submatches: Vec<String> = line
+            .hits
+            .iter()
+            .map(|hit| {
+                let range = raw_range(line, &hit.range);
+                format!(
+                    "{{\"match\":{},\"start\":{},\"end\":{},\"pattern\":{}}}",
+                    json::data(&line.bytes[range.clone()]),
+                    range.start,
+                    range.end,
+                    hit.pattern
+                )
+            })
+            .collect();
+        let distance = line
+            .distance
+            .map_or(String::new(), |d| format!(",\"distance\":{d}"));
+        writeln!(
+            out,
+            "{{\"type\":\"{kind}\",\"data\":{{\"path\":{},\"lines\":{},\"line_number\":{},\"absolute_offset\":{},\"submatches\":[{}]{distance}}}}}",
+            path_json(path),
+            json::data(line.bytes),
+            line.number,
+            line.offset,
+            submatches.join(",")
+        )
+    }
+
+    pub fn write_binary_match(&self, out: &mut impl Write, path: &Path) -> io::Result<()> {
+        writeln!(out, "Binary file {} matches", display_path(path))
+    }
+
+    // 文件搜索结束：-c/-l 在这里输出结果，--json 输出 end 事件
+    pub fn end(&self, out: &mut impl Write, path: &Path, stats: &Stats) -> io::Result<()> {
+        match self.mode {
+            // -c：和 grep 一样，没有匹配的文件也会输出 0
+            OutputMode::Count if self.with_path => {
+                self.write_field(out, PATH_COLOR, &display_path(path), ':')?;
+                writeln!(out, "{}", stats.matched_lines)
+            }
+            OutputMode::Count => writeln!(out, "{}", stats.matched_lines),
+            OutputMode::FilesWithMatches if stats.matched_lines > 0 && self.color => {
+                writeln!(out, "{PATH_COLOR}{}{RESET}", display_path(path))
+            }
+            OutputMode::FilesWithMatches if stats.matched_lines > 0 => {
+                writeln!(out, "{}", display_path(path))
+            }
+            OutputMode::Json => writeln!(
+                out,
+                "{{\"type\":\"end\",\"data\":{{\"path\":{},\"stats\":{}}}}}",
+                path_json(path),
+                stats.to_json()
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    // 所有文件搜索完毕，--json 时输出汇总
+    pub fn summary(
+        &self,
+        out: &mut impl Write,
+        stats: &Stats,
+        elapsed: Duration,
+    ) -> io::Result<()> {
+        if self.mode == OutputMode::Json {
+            writeln!(
+                out,
+                "{{\"type\":\"summary\",\"data\":{{\"elapsed_total\":{},\"stats\":{}}}}}",
+                elapsed_json(elapsed),
+                stats.to_json()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// 命令行的输出方式：把一个文件的搜索结果按 Printer 的设置写进 out
+pub struct PrinterSink<'a, W> {
+    printer: &'a Printer,
+    path: &'a Path,
+    out: &'a mut W,
+    // --sort distance 时先收集所有匹配行，搜索结束再排序输出
+    ranked: Vec<RankedLine>,
+}
+
+// 暂存的匹配行
+struct RankedLine {
+    distance: Option<usize>,
+    number: usize,
+    offset: usize,
+    text: String,
+    bytes: Vec<u8>,
+    hits: Vec<Hit>,
+}
+
+impl<'a, W: Write> PrinterSink<'a, W> {
+    pub fn new(printer: &'a Printer, path: &'a Path, out: &'a mut W) -> PrinterSink<'a, W> {
+        PrinterSink {
+            printer,
+            path,
+            out,
+            ranked: Vec::new(),
+        }
+    }
+}
+
+impl<W: Write> Sink for PrinterSink<'_, W> {
+    fn matched(&mut self, line: &Line) -> io::Result<bool> {
+        match self.printer.mode {
+            // -l 只需要知道有没有匹配
+            OutputMode::FilesWithMatches => return Ok(false),
+            OutputMode::Count => {}
+            _ if self.printer.rank => self.ranked.push(RankedLine {
+                distance: line.distance,
+                number: line.number,
+                offset: line.offset,
+                text: line.text.to_string(),
+                bytes: line.bytes.to_vec(),
+                hits: line.hits.to_vec(),
+            }),
+            _ => self.printer.write_line(self.out, self.path, line)?,
+        }
+        Ok(true)
+    }
+
+    fn context(&mut self, line: &Line) -> io::Result<bool> {
+        if self.printer.prints_lines() {
+            self.printer.write_line(self.out, self.path, line)?;
+        }
+        Ok(true)
+    }
+
+    fn context_break(&mut self) -> io::Result<()> {
+        if self.printer.has_context() {
+            writeln!(self.out, "--")?;
+        }
+        Ok(())
+    }
+
+    // 二进制文件不输出具体的行，只提示有匹配
+    fn binary_match(&mut self, line: &Line) -> io::Result<bool> {
+        if self.printer.mode == OutputMode::Lines {
+            self.printer.write_binary_match(self.out, self.path)?;
+            return Ok(false);
+        }
+        self.matched(line)
+    }
+
+    fn begin(&mut self) -> io::Result<()> {
+        self.printer.begin(self.out, self.path)
+    }
+
+    fn finish(&mut self, stats: &Stats) -> io::Result<()> {
+        // 稳定排序，距离相同的行保持原来的顺序
+        self.ranked.sort_by_key(|line| line.distance);
+        for line in &self.ranked {
+            self.printer.write_line(
+                self.out,
+                self.path,
+                &Line {
+                    number: line.number,
+                    offset: line.offset,
+                    text: &line.text,
+                    bytes: &line.bytes,
+                    hits: &line.hits,
+                    is_match: true,
+                    distance: line.distance,
+                },
+            )?;
+        }
+        self.printer.end(self.out, self.path, stats)
+    }
+}
+
+// 把 text 里的字节范围换算成 bytes 里的范围
+// text 是 bytes 按 String::from_utf8_lossy 解码的结果：每一段不合法的字节变成一个 U+FFFD
+fn raw_range(line: &Line, range: &Range<usize>) -> Range<usize> {
+    if line.bytes == line.text.as_bytes() {
+        return range.clone();
+    }
+    let raw = |offset: usize| {
+        let (mut text_pos, mut raw_pos) = (0, 0);
+        for chunk in line.bytes.utf8_chunks() {
+            let valid = chunk.valid().len();
+            if offset <= text_pos + valid {
+                return raw_pos + offset - text_pos;
+            }
+            text_pos += valid;
+            raw_pos += valid;
+            let invalid = chunk.invalid().len();
+            if invalid > 0 {
+                // 落在 U+FFFD 中间的位置算作这段字节的开头
+                if offset < text_pos + '\u{fffd}'.len_utf8() {
+                    return raw_pos;
+                }
+                text_pos += '\u{fffd}'.len_utf8();
+                raw_pos += invalid;
+            }
+        }
+        line.bytes.len()
+    };
+    raw(range.start)..raw(range.end)
+}
+
+// 路径 "-" 表示标准输入，输出时沿用 grep 的叫法
+pub fn display_path(path: &Path) -> String {
+    if path == Path::new("-") {
+        "(standard input)".to_string()
+    } else {
+        path.display().to_string()
+    }
+}
+
+fn path_json(path: &Path) -> String {
+    if path == Path::new("-") {
+        json::data(b"<stdin>")
+    } else {
+        // 路径不一定是合法 UTF-8，交给 json::data 决定用 text 还是 bytes
+        json::data(path.as_os_str().as_encoded_bytes())
+    }
+}
+
+fn elapsed_json(elapsed: Duration) -> String {
+    format!(
+        "{{\"secs\":{},\"nanos\":{},\"human\":\"{:.6}s\"}}",
+        elapsed.as_secs(),
+        elapsed.subsec_nanos(),
+        elapsed.as_secs_f64()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn highlights_spans() {
+        let config = Config {
+            line_number: true,
+            ..Config::default()
+        };
+        let printer = Printer::new(&config, false).with_color(true);
+        let line = Line {
+            number: 3,
+            offset: 0,
+            text: "Are you nobody, too?",
+            bytes: b"Are you nobody, too?",
+            hits: &[
+                Hit {
+                    range: 4..7,
+                    pattern: 0,
+                },
+                Hit {
+                    range: 16..19,
+                    pattern: 0,
+                },
+            ],
+            is_match: true,
+            distance: None,
+        };
+        let mut out = Vec::new();
+        printer
+            .write_line(&mut out, Path::new("poem.txt"), &line)
+            .unwrap();
+
+        assert_eq!(
+            "\x1b[32m3\x1b[0m\x1b[36m:\x1b[0mAre \x1b[1;31myou\x1b[0m nobody, \x1b[1;31mtoo\x1b[0m?\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn json_keeps_invalid_bytes() {
+        let config = Config {
+            output: OutputMode::Json,
+            ..Config::default()
+        };
+        let bytes = b"\xe9t\xe9 caf\xe9";
+        let text = String::from_utf8_lossy(bytes);
+        let line = Line {
+            number: 1,
+            offset: 0,
+            text: &text,
+            bytes,
+            hits: &[Hit {
+                range: 8..11,
+                pattern: 0,
+            }],
+            is_match: true,
+            distance: None,
+        };
+        let mut out = Vec::new();
+        Printer::new(&config, false)
+            .write_line(&mut out, Path::new("-"), &line)
+            .unwrap();
+
+        assert!(String::from_utf8(out).unwrap().contains(
+            r#""lines":{"bytes":"6XTpIGNhZuk="},"line_number":1,"absolute_offset":0,"submatches":[{"match":{"text":"caf"},"start":4,"end":7,"pattern":0}]"#
+        ));
+    }
+}
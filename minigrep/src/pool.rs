@@ -0,0 +1,89 @@
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+// 固定大小的线程池：所有 worker 共享同一个任务队列（Receiver 放在 Mutex 里）
+pub struct ThreadPool {
+    workers: Vec<Worker>,
+    sender: Option<mpsc::Sender<Job>>,
+}
+
+impl ThreadPool {
+    // size 为 0 时没有线程可以执行任务，直接 panic
+    pub fn new(size: usize) -> ThreadPool {
+        assert!(size > 0);
+
+        let (sender, receiver) = mpsc::channel();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..size)
+            .map(|_| Worker::new(Arc::clone(&receiver)))
+            .collect();
+
+        ThreadPool {
+            workers,
+            sender: Some(sender),
+        }
+    }
+
+    pub fn execute<F>(&self, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.sender.as_ref().unwrap().send(Box::new(f)).unwrap();
+    }
+}
+
+impl Drop for ThreadPool {
+    // 先关闭发送端，worker 的 recv 返回 Err 后退出循环，再逐个 join
+    fn drop(&mut self) {
+        drop(self.sender.take());
+        for worker in &mut self.workers {
+            if let Some(thread) = worker.thread.take() {
+                thread.join().unwrap();
+            }
+        }
+    }
+}
+
+struct Worker {
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Worker {
+    fn new(receiver: Arc<Mutex<mpsc::Receiver<Job>>>) -> Worker {
+        let thread = thread::spawn(move || loop {
+            // 锁只在取任务时持有，执行任务时其它 worker 可以继续取
+            let message = receiver.lock().unwrap().recv();
+            match message {
+                Ok(job) => job(),
+                Err(_) => break,
+            }
+        });
+
+        Worker {
+            thread: Some(thread),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_every_job() {
+        let (tx, rx) = mpsc::channel();
+        let pool = ThreadPool::new(3);
+        for i in 0..10 {
+            let tx = tx.clone();
+            pool.execute(move || tx.send(i).unwrap());
+        }
+        drop(tx);
+        drop(pool);
+
+        let mut results: Vec<i32> = rx.iter().collect();
+        results.sort();
+        assert_eq!((0..10).collect::<Vec<_>>(), results);
+    }
+}
@@ -0,0 +1,491 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::fold::CaseFolding;
+use crate::matcher::{is_word_char, Hit, Matcher};
+
+// --bool：由 AND、OR、NOT、NEAR/N 和括号组成的查询，例如
+//   timeout AND retry AND NOT debug
+//   (panic OR fatal) AND "connection reset"
+//   timeout NEAR/3 retry
+// 运算符必须大写，小写的 and/or 只是普通的词；优先级从高到低是 NEAR、NOT、AND、OR：
+// NEAR 的两边只能是词，NOT a NEAR/2 b 表示 NOT (a NEAR/2 b)
+// 词按字面量匹配，包含空格、括号或者和运算符同名的词要放在双引号里
+pub struct Query {
+    root: Node,
+    terms: Vec<Matcher>,
+    // 不在 NOT 下面的词，行被选中时高亮它们
+    positive: Vec<usize>,
+}
+
+enum Node {
+    // terms 里的下标
+    Term(usize),
+    Not(Box<Node>),
+    And(Vec<Node>),
+    Or(Vec<Node>),
+    // 两个词出现的位置相差不超过 distance 个词
+    Near {
+        left: usize,
+        right: usize,
+        distance: usize,
+    },
+}
+
+// 查询语法错误；position 是出错位置在查询中的字节偏移，显示时用 ^ 标出来
+#[derive(Debug, PartialEq)]
+pub struct QueryError {
+    query: String,
+    position: usize,
+    message: String,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let column = self.query[..self.position].chars().count();
+        writeln!(f, "{}", self.message)?;
+        writeln!(f, "  {}", self.query)?;
+        write!(f, "  {}^", " ".repeat(column))
+    }
+}
+
+impl Error for QueryError {}
+
+impl Query {
+    // 词的匹配方式和普通的字面量一样：ignore_case 时按 folding 折叠，word 时要求整词匹配
+    pub fn parse(
+        query: &str,
+        ignore_case: bool,
+        folding: CaseFolding,
+        word: bool,
+    ) -> Result<Query, QueryError> {
+        let tokens = tokenize(query)?;
+        let mut parser = Parser {
+            query,
+            tokens,
+            pos: 0,
+            terms: Vec::new(),
+        };
+        let root = parser.parse()?;
+
+        let mut positive = Vec::new();
+        root.positive_terms(false, &mut positive);
+        positive.sort_unstable();
+        positive.dedup();
+        let terms = parser
+            .terms
+            .iter()
+            .map(|term| {
+                let matcher = if ignore_case {
+                    Matcher::folded(term, folding)
+                } else {
+                    Matcher::literal(term, false)
+                };
+                matcher.word(word)
+            })
+            .collect();
+        Ok(Query {
+            root,
+            terms,
+            positive,
+        })
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        let mut hits = vec![None; self.terms.len()];
+        self.root.eval(text, &self.terms, &mut hits)
+    }
+
+    // 查询成立时返回所有肯定的词在 text 中出现的位置，否则为空
+    pub fn find_hits(&self, text: &str) -> Vec<Hit> {
+        if self.is_match(text) {
+            self.term_hits(text)
+        } else {
+            Vec::new()
+        }
+    }
+
+    // 不判断查询是否成立，只找出肯定的词的位置；--scope paragraph 用它高亮段落里的每一行
+    pub fn term_hits(&self, text: &str) -> Vec<Hit> {
+        let mut hits: Vec<Hit> = self
+            .positive
+            .iter()
+            .flat_map(|&i| self.terms[i].find_hits(text))
+            .map(|hit| Hit {
+                range: hit.range,
+                pattern: 0,
+            })
+            .collect();
+        hits.sort_by_key(|hit| (hit.range.start, std::cmp::Reverse(hit.range.end)));
+        // 不同的词可能重叠（例如 "time" 和 "timeout"），只保留互不重叠的
+        let mut end = 0;
+        hits.retain(|hit| {
+            let keep = hit.range.start >= end;
+            if keep {
+                end = hit.range.end;
+            }
+            keep
+        });
+        hits
+    }
+}
+
+impl Node {
+    fn positive_terms(&self, negated: bool, out: &mut Vec<usize>) {
+        match self {
+            Node::Term(i) if !negated => out.push(*i),
+            Node::Term(_) => {}
+            Node::Not(node) => node.positive_terms(!negated, out),
+            Node::And(nodes) | Node::Or(nodes) => {
+                for node in nodes {
+                    node.positive_terms(negated, out);
+                }
+            }
+            Node::Near { left, right, .. } if !negated => out.extend([*left, *right]),
+            Node::Near { .. } => {}
+        }
+    }
+
+    // hits 缓存每个词的匹配位置，同一个词在查询里出现多次时只查找一次
+    fn eval(&self, text: &str, terms: &[Matcher], hits: &mut [Option<Vec<Hit>>]) -> bool {
+        match self {
+            Node::Term(i) => !term_hits(text, terms, hits, *i).is_empty(),
+            Node::Not(node) => !node.eval(text, terms, hits),
+            Node::And(nodes) => nodes.iter().all(|node| node.eval(text, terms, hits)),
+            Node::Or(nodes) => nodes.iter().any(|node| node.eval(text, terms, hits)),
+            Node::Near {
+                left,
+                right,
+                distance,
+            } => {
+                let lefts = word_positions(text, term_hits(text, terms, hits, *left));
+                let rights = word_positions(text, term_hits(text, terms, hits, *right));
+                lefts
+                    .iter()
+                    .any(|l| rights.iter().any(|r| l.abs_diff(*r) <= *distance))
+            }
+        }
+    }
+}
+
+fn term_hits<'h>(
+    text: &str,
+    terms: &[Matcher],
+    hits: &'h mut [Option<Vec<Hit>>],
+    i: usize,
+) -> &'h [Hit] {
+    hits[i].get_or_insert_with(|| terms[i].find_hits(text))
+}
+
+// 每处匹配开头是 text 里的第几个词（从 0 开始）
+fn word_positions(text: &str, hits: &[Hit]) -> Vec<usize> {
+    let mut words = 0;
+    let mut in_word = false;
+    let mut positions = Vec::with_capacity(hits.len());
+    let mut next = hits.iter().peekable();
+    for (i, c) in text.char_indices() {
+        // 匹配从词中间开始时算作这个词
+        while next.next_if(|hit| hit.range.start <= i).is_some() {
+            positions.push(if in_word { words - 1 } else { words });
+        }
+        let word = is_word_char(c);
+        if word && !in_word {
+            words += 1;
+        }
+        in_word = word;
+    }
+    positions.extend(next.map(|_| words));
+    positions
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Term(String),
+    And,
+    Or,
+    Not,
+    Near(usize),
+    Open,
+    Close,
+}
+
+// 把查询切成 (位置, 词法单元)
+fn tokenize(query: &str) -> Result<Vec<(usize, Token)>, QueryError> {
+    let mut tokens = Vec::new();
+    let mut chars = query.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        match c {
+            _ if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' => {
+                chars.next();
+                let token = if c == '(' { Token::Open } else { Token::Close };
+                tokens.push((start, token));
+            }
+            '"' => {
+                chars.next();
+                let mut term = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, c)) => term.push(c),
+                            None => return Err(error(query, start, "unterminated quote")),
+                        },
+                        Some((_, c)) => term.push(c),
+                        None => return Err(error(query, start, "unterminated quote")),
+                    }
+                }
+                if term.is_empty() {
+                    return Err(error(query, start, "empty quoted term"));
+                }
+                tokens.push((start, Token::Term(term)));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&(_, c)) = chars.peek() {
+                    if c.is_whitespace() || matches!(c, '(' | ')' | '"') {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                let token = match word.as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => match word.strip_prefix("NEAR/") {
+                        Some(n) => Token::Near(n.parse().map_err(|_| {
+                            error(query, start + 5, "NEAR/ must be followed by a number")
+                        })?),
+                        None if word == "NEAR" => {
+                            return Err(error(query, start, "NEAR needs a distance, e.g. NEAR/5"))
+                        }
+                        None => Token::Term(word),
+                    },
+                };
+                tokens.push((start, token));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn error(query: &str, position: usize, message: &str) -> QueryError {
+    QueryError {
+        query: query.to_string(),
+        position,
+        message: message.to_string(),
+    }
+}
+
+// 递归下降：or := and (OR and)*，and := unary (AND unary)*，
+// unary := NOT unary | near，near := atom (NEAR/N atom)*，atom := TERM | ( or )
+struct Parser<'q> {
+    query: &'q str,
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    terms: Vec<String>,
+}
+
+impl Parser<'_> {
+    fn parse(&mut self) -> Result<Node, QueryError> {
+        if self.tokens.is_empty() {
+            return Err(error(self.query, 0, "empty query"));
+        }
+        let node = self.or()?;
+        match self.tokens.get(self.pos) {
+            None => Ok(node),
+            Some((at, Token::Close)) => Err(error(self.query, *at, "unmatched ')'")),
+            Some((at, _)) => Err(error(
+                self.query,
+                *at,
+                "expected AND, OR or NEAR/N between terms",
+            )),
+        }
+    }
+
+    fn or(&mut self) -> Result<Node, QueryError> {
+        let mut nodes = vec![self.and()?];
+        while self.eat(&Token::Or) {
+            nodes.push(self.and()?);
+        }
+        Ok(if nodes.len() == 1 {
+            nodes.pop().unwrap()
+        } else {
+            Node::Or(nodes)
+        })
+    }
+
+    fn and(&mut self) -> Result<Node, QueryError> {
+        let mut nodes = vec![self.unary()?];
+        while self.eat(&Token::And) {
+            nodes.push(self.unary()?);
+        }
+        Ok(if nodes.len() == 1 {
+            nodes.pop().unwrap()
+        } else {
+            Node::And(nodes)
+        })
+    }
+
+    fn unary(&mut self) -> Result<Node, QueryError> {
+        if self.eat(&Token::Not) {
+            return Ok(Node::Not(Box::new(self.unary()?)));
+        }
+        self.near()
+    }
+
+    // a NEAR/2 b NEAR/2 c 表示相邻的两个词都要足够近
+    fn near(&mut self) -> Result<Node, QueryError> {
+        let start = self.position();
+        let first = self.atom()?;
+        let mut nodes = Vec::new();
+        let mut left = (start, first);
+        while let Some((_, Token::Near(distance))) = self.tokens.get(self.pos) {
+            let distance = *distance;
+            self.pos += 1;
+            let right_start = self.position();
+            let right = self.atom()?;
+            let (Node::Term(l), Node::Term(r)) = (&left.1, &right) else {
+                let bad = if matches!(left.1, Node::Term(_)) {
+                    right_start
+                } else {
+                    left.0
+                };
+                return Err(error(self.query, bad, "NEAR/N can only join two terms"));
+            };
+            nodes.push(Node::Near {
+                left: *l,
+                right: *r,
+                distance,
+            });
+            left = (right_start, right);
+        }
+        Ok(match nodes.len() {
+            0 => left.1,
+            1 => nodes.pop().unwrap(),
+            _ => Node::And(nodes),
+        })
+    }
+
+    fn atom(&mut self) -> Result<Node, QueryError> {
+        let at = self.position();
+        match self.tokens.get(self.pos) {
+            Some((_, Token::Term(term))) => {
+                let term = term.clone();
+                self.pos += 1;
+                let index = match self.terms.iter().position(|t| *t == term) {
+                    Some(index) => index,
+                    None => {
+                        self.terms.push(term);
+                        self.terms.len() - 1
+                    }
+                };
+                Ok(Node::Term(index))
+            }
+            Some((_, Token::Open)) => {
+                self.pos += 1;
+                let node = self.or()?;
+                if !self.eat(&Token::Close) {
+                    return Err(error(self.query, at, "unclosed '('"));
+                }
+                Ok(node)
+            }
+            _ => Err(error(self.query, at, "expected a term")),
+        }
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        let found = self.tokens.get(self.pos).is_some_and(|(_, t)| t == token);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    // 下一个词法单元的位置；已经到结尾时指向查询末尾
+    fn position(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .map_or(self.query.len(), |(at, _)| *at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(q: &str) -> Query {
+        Query::parse(q, false, CaseFolding::Full, false).unwrap()
+    }
+
+    #[test]
+    fn evaluates_and_or_not() {
+        let q = query("timeout AND retry AND NOT debug");
+        assert!(q.is_match("retry after timeout"));
+        assert!(!q.is_match("debug: retry after timeout"));
+        assert!(!q.is_match("timeout"));
+
+        let q = query(r#"(panic OR fatal) AND "connection reset""#);
+        assert!(q.is_match("fatal: connection reset by peer"));
+        assert!(!q.is_match("panic: connection refused"));
+
+        let q = Query::parse("NOT Error OR and", true, CaseFolding::Full, false).unwrap();
+        assert!(q.is_match("all good"));
+        assert!(q.is_match("ERROR and more"));
+        assert!(!q.is_match("ERROR"));
+    }
+
+    #[test]
+    fn near_counts_words() {
+        let q = query("timeout NEAR/2 retry");
+        assert!(q.is_match("timeout, will retry"));
+        assert!(q.is_match("retry on timeout"));
+        assert!(!q.is_match("timeout and then we retry"));
+
+        let q = query("NOT timeout NEAR/1 retry");
+        assert!(q.is_match("timeout and then retry"));
+        assert!(!q.is_match("timeout retry"));
+
+        let q = query("timeout AND NOT debug");
+        assert_eq!(
+            vec![4..11],
+            q.find_hits("the timeout")
+                .into_iter()
+                .map(|h| h.range)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn syntax_errors_point_at_the_problem() {
+        let err = Query::parse("timeout AND", false, CaseFolding::Full, false)
+            .err()
+            .unwrap();
+        assert_eq!(
+            "expected a term\n  timeout AND\n             ^",
+            err.to_string()
+        );
+
+        let cases = [
+            ("(a OR b", 0, "unclosed '('"),
+            ("a b", 2, "expected AND, OR or NEAR/N between terms"),
+            ("a AND \"b", 6, "unterminated quote"),
+            ("(a OR b) NEAR/3 c", 0, "NEAR/N can only join two terms"),
+            ("a NEAR/x b", 7, "NEAR/ must be followed by a number"),
+            ("a)", 1, "unmatched ')'"),
+        ];
+        for (q, position, message) in cases {
+            let err = Query::parse(q, false, CaseFolding::Full, false)
+                .err()
+                .unwrap();
+            assert_eq!(
+                (position, message),
+                (err.position, err.message.as_str()),
+                "{q}"
+            );
+        }
+    }
+}
@@ -0,0 +1,732 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use encoding_rs::Encoding;
+use encoding_rs_io::{DecodeReaderBytes, DecodeReaderBytesBuilder};
+
+use crate::output::{Line, Stats};
+use crate::stream::{self, SearchOptions};
+use crate::{CaseFolding, Match, Matcher, MinigrepError};
+
+// 模式的解释方式
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum PatternKind {
+    #[default]
+    Literal,
+    Regex,
+    // 编辑距离不超过给定值的近似匹配，只支持一个模式
+    Fuzzy(usize),
+    // AND/OR/NOT/NEAR 组成的布尔查询，只支持一个模式
+    Boolean,
+}
+
+// 模式在什么范围内求值
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Scope {
+    #[default]
+    Line,
+    // 以空行分隔的段落：段落满足模式时输出其中的每一行
+    Paragraph,
+}
+
+// 搜索结果的接收方：命令行输出是其中一种实现，嵌入 minigrep 的程序可以自己实现
+// 返回 Ok(false) 表示不再需要当前输入后面的内容
+pub trait Sink {
+    // 被选中的行（普通匹配或者 -v 选出的行）
+    fn matched(&mut self, line: &Line) -> io::Result<bool>;
+
+    // -A/-B 的上下文行
+    fn context(&mut self, _line: &Line) -> io::Result<bool> {
+        Ok(true)
+    }
+
+    // 两段不相邻的上下文之间
+    fn context_break(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    // 在二进制输入里选中了一行，默认和普通的匹配行一样处理
+    fn binary_match(&mut self, line: &Line) -> io::Result<bool> {
+        self.matched(line)
+    }
+
+    fn begin(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    // 输入搜索完毕（包括提前停止）之后调用一次
+    fn finish(&mut self, _stats: &Stats) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// 转发给内部的 sink，并记下 sink 是否返回过错误
+// 读取输入和 sink 的错误都是 io::Error，只能靠这个区分
+struct Tracked<'s, S> {
+    sink: &'s mut S,
+    failed: bool,
+}
+
+impl<S> Tracked<'_, S> {
+    fn check<T>(&mut self, result: io::Result<T>) -> io::Result<T> {
+        self.failed |= result.is_err();
+        result
+    }
+}
+
+impl<S: Sink> Sink for Tracked<'_, S> {
+    fn matched(&mut self, line: &Line) -> io::Result<bool> {
+        let result = self.sink.matched(line);
+        self.check(result)
+    }
+
+    fn context(&mut self, line: &Line) -> io::Result<bool> {
+        let result = self.sink.context(line);
+        self.check(result)
+    }
+
+    fn context_break(&mut self) -> io::Result<()> {
+        let result = self.sink.context_break();
+        self.check(result)
+    }
+
+    fn binary_match(&mut self, line: &Line) -> io::Result<bool> {
+        let result = self.sink.binary_match(line);
+        self.check(result)
+    }
+
+    fn begin(&mut self) -> io::Result<()> {
+        let result = self.sink.begin();
+        self.check(result)
+    }
+
+    fn finish(&mut self, stats: &Stats) -> io::Result<()> {
+        let result = self.sink.finish(stats);
+        self.check(result)
+    }
+}
+
+// 构造 Searcher：先设置模式和各种选项，再调用 build 编译模式
+#[derive(Debug, Default, Clone)]
+pub struct SearcherBuilder {
+    patterns: Vec<String>,
+    kind: PatternKind,
+    ignore_case: bool,
+    case_folding: CaseFolding,
+    word: bool,
+    before_context: usize,
+    after_context: usize,
+    options: SearchOptions,
+}
+
+impl SearcherBuilder {
+    pub fn new() -> SearcherBuilder {
+        SearcherBuilder::default()
+    }
+
+    // 添加一个模式；多个模式之间是“或”的关系
+    pub fn pattern(mut self, pattern: impl Into<String>) -> SearcherBuilder {
+        self.patterns.push(pattern.into());
+        self
+    }
+
+    pub fn patterns<I, S>(mut self, patterns: I) -> SearcherBuilder
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.patterns.extend(patterns.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn kind(mut self, kind: PatternKind) -> SearcherBuilder {
+        self.kind = kind;
+        self
+    }
+
+    pub fn ignore_case(mut self, yes: bool) -> SearcherBuilder {
+        self.ignore_case = yes;
+        self
+    }
+
+    // 忽略大小写时字面量使用的折叠方式，默认是 Full
+    pub fn case_folding(mut self, folding: CaseFolding) -> SearcherBuilder {
+        self.case_folding = folding;
+        self
+    }
+
+    pub fn word(mut self, yes: bool) -> SearcherBuilder {
+        self.word = yes;
+        self
+    }
+
+    pub fn invert(mut self, yes: bool) -> SearcherBuilder {
+        self.options.invert = yes;
+        self
+    }
+
+    pub fn before_context(mut self, lines: usize) -> SearcherBuilder {
+        self.before_context = lines;
+        self
+    }
+
+    pub fn after_context(mut self, lines: usize) -> SearcherBuilder {
+        self.after_context = lines;
+        self
+    }
+
+    // 每个输入最多选出多少行
+    pub fn max_count(mut self, max: Option<usize>) -> SearcherBuilder {
+        self.options.max_count = max;
+        self
+    }
+
+    // 交给 Sink 的匹配行先做替换，正则模式下可以引用分组
+    pub fn replace(mut self, replacement: Option<String>) -> SearcherBuilder {
+        self.options.replace = replacement;
+        self
+    }
+
+    // 为 true 时不检测二进制输入
+    pub fn text(mut self, yes: bool) -> SearcherBuilder {
+        self.options.text = yes;
+        self
+    }
+
+    // 读取 Read 和路径时使用的编码，None 表示根据 BOM 识别 UTF-8/UTF-16
+    pub fn encoding(mut self, encoding: Option<&'static Encoding>) -> SearcherBuilder {
+        self.options.encoding = encoding;
+        self
+    }
+
+    // 让匹配可以跨越换行符：正则里的 ^/$ 匹配每一行的首尾，\n 和 \s 可以匹配换行
+    // 每一处匹配覆盖的所有行都会交给 Sink；-m 限制的是匹配的个数而不是行数
+    // matches 系列的迭代器这时会先搜索完整个输入，再逐个返回被覆盖的行
+    pub fn multiline(mut self, yes: bool) -> SearcherBuilder {
+        self.options.multiline = yes;
+        self
+    }
+
+    // 按段落求值时整个输入会被读进内存
+    pub fn scope(mut self, scope: Scope) -> SearcherBuilder {
+        self.options.scope = scope;
+        self
+    }
+
+    // 模式有误（例如正则语法错误）时返回 MinigrepError::Pattern
+    pub fn build(&self) -> Result<Searcher, MinigrepError> {
+        let case = self.ignore_case;
+        if self.options.multiline
+            && (self.options.replace.is_some() || matches!(self.kind, PatternKind::Fuzzy(_)))
+        {
+            return Err(MinigrepError::Usage(
+                "multiline search can't be combined with replace or fuzzy matching".to_string(),
+            ));
+        }
+        if self.options.scope == Scope::Paragraph
+            && (self.options.multiline
+                || self.options.replace.is_some()
+                || matches!(self.kind, PatternKind::Fuzzy(_)))
+        {
+            return Err(MinigrepError::Usage(
+                "paragraph scope can't be combined with multiline search, replace or fuzzy matching"
+                    .to_string(),
+            ));
+        }
+        let multiline_patterns: Vec<String>;
+        let patterns = if self.options.multiline && self.kind == PatternKind::Regex {
+            multiline_patterns = self.patterns.iter().map(|p| format!("(?m){p}")).collect();
+            &multiline_patterns
+        } else {
+            &self.patterns
+        };
+        let matcher = match (&patterns[..], self.kind) {
+            ([query], PatternKind::Fuzzy(max)) => Matcher::fuzzy(query, max, case),
+            (_, PatternKind::Fuzzy(_)) => {
+                return Err(MinigrepError::Usage(
+                    "fuzzy matching takes exactly one pattern".to_string(),
+                ))
+            }
+            ([query], PatternKind::Boolean) => {
+                Matcher::query(query, case, self.case_folding, self.word)?
+            }
+            (_, PatternKind::Boolean) => {
+                return Err(MinigrepError::Usage(
+                    "a boolean query takes exactly one pattern".to_string(),
+                ))
+            }
+            ([query], PatternKind::Regex) => Matcher::regex(query, case)?,
+            ([query], PatternKind::Literal) if case => Matcher::folded(query, self.case_folding),
+            ([query], PatternKind::Literal) => Matcher::literal(query, false),
+            (patterns, PatternKind::Regex) => Matcher::regex_multi(patterns, case)?,
+            (patterns, PatternKind::Literal) if case => {
+                Matcher::multi_folded(patterns, self.case_folding)?
+            }
+            (patterns, PatternKind::Literal) => Matcher::multi(patterns, false)?,
+        }
+        // 布尔查询的 -w 已经作用在每个词上
+        .word(self.word && self.kind != PatternKind::Boolean);
+        Ok(Searcher {
+            matcher,
+            options: self.options.clone(),
+            before_context: self.before_context,
+            after_context: self.after_context,
+        })
+    }
+}
+
+// 编译好的搜索器，可以在多个线程之间共享
+pub struct Searcher {
+    matcher: Matcher,
+    options: SearchOptions,
+    before_context: usize,
+    after_context: usize,
+}
+
+impl Searcher {
+    pub(crate) fn matcher(&self) -> &Matcher {
+        &self.matcher
+    }
+
+    pub(crate) fn options(&self) -> &SearchOptions {
+        &self.options
+    }
+
+    pub fn before_context(&self) -> usize {
+        self.before_context
+    }
+
+    pub fn after_context(&self) -> usize {
+        self.after_context
+    }
+
+    // 惰性地逐行匹配 text，每次 next 只扫描到下一个被选中的行
+    // -U 和 --scope paragraph 需要整个输入，第一次调用 next 时一次搜索完
+    pub fn matches<'s, 'a>(&'s self, text: &'a str) -> Matches<'s, 'a> {
+        Matches {
+            searcher: self,
+            text,
+            whole: None,
+            rest: text,
+            line_number: 0,
+            offset: 0,
+            selected: 0,
+        }
+    }
+
+    // 和 matches 一样，但从 Read 中按行读取；输入会先按 BOM 或 encoding 转码
+    pub fn matches_reader<R: Read>(&self, reader: R) -> ReaderMatches<'_, Decoded<R>> {
+        ReaderMatches::new(self, self.decode(reader), None)
+    }
+
+    // 打开 path 并逐行匹配，读取出错时错误里带着路径
+    pub fn matches_path(
+        &self,
+        path: &Path,
+    ) -> Result<ReaderMatches<'_, Decoded<File>>, MinigrepError> {
+        let file = File::open(path).map_err(|err| MinigrepError::io(path, err))?;
+        Ok(ReaderMatches::new(
+            self,
+            self.decode(file),
+            Some(path.to_path_buf()),
+        ))
+    }
+
+    // 把 text 的搜索结果交给 sink，返回统计信息
+    pub fn search_str<S: Sink>(&self, text: &str, sink: &mut S) -> io::Result<Stats> {
+        stream::search_reader(text.as_bytes(), self, sink)
+    }
+
+    pub fn search_reader<R: Read, S: Sink>(&self, reader: R, sink: &mut S) -> io::Result<Stats> {
+        stream::search_reader(self.decode(reader), self, sink)
+    }
+
+    // 路径 "-" 表示标准输入
+    pub fn search_path<S: Sink>(&self, path: &Path, sink: &mut S) -> Result<Stats, MinigrepError> {
+        if path == Path::new("-") {
+            return self.search_input(path, io::stdin().lock(), sink);
+        }
+        let file = File::open(path).map_err(|err| MinigrepError::io(path, err))?;
+        self.search_input(path, file, sink)
+    }
+
+    // 和 search_reader 一样，但把错误分成读取 path 失败和 sink 写输出失败两种
+    pub(crate) fn search_input<R: Read, S: Sink>(
+        &self,
+        path: &Path,
+        reader: R,
+        sink: &mut S,
+    ) -> Result<Stats, MinigrepError> {
+        let mut tracked = Tracked {
+            sink,
+            failed: false,
+        };
+        self.search_reader(reader, &mut tracked).map_err(|err| {
+            if tracked.failed {
+                MinigrepError::Output(err)
+            } else {
+                MinigrepError::io(path, err)
+            }
+        })
+    }
+
+    fn decode<R: Read>(&self, reader: R) -> Decoded<R> {
+        let mut decoder = DecodeReaderBytesBuilder::new();
+        decoder.encoding(self.options.encoding).bom_override(true);
+        BufReader::new(decoder.build(reader))
+    }
+
+    fn select<'a>(
+        &self,
+        line_number: usize,
+        byte_offset: usize,
+        line: &'a str,
+    ) -> Option<Match<'a>> {
+        if self.options.invert {
+            return (!self.matcher.is_match(line)).then(|| Match {
+                line_number,
+                byte_offset,
+                line,
+                spans: Vec::new(),
+                patterns: Vec::new(),
+            });
+        }
+        // 和 stream 一样由 is_match 决定是否选中：只有 NOT 的布尔查询选中的行没有可以高亮的位置
+        if !self.matcher.is_match(line) {
+            return None;
+        }
+        let hits = self.matcher.highlights(line);
+        Some(Match::from_hits(line_number, byte_offset, line, hits))
+    }
+
+    // 匹配可能跨行（-U）或者要看整个段落，不能逐行判断
+    fn needs_whole_input(&self) -> bool {
+        self.options.multiline || self.options.scope == Scope::Paragraph
+    }
+
+    // 用和 search_reader 相同的规则搜索整个输入，收集被选中的行
+    fn select_all<R: BufRead>(&self, reader: R) -> io::Result<Vec<OwnedMatch>> {
+        let mut selected = Selected(Vec::new());
+        stream::search_reader(reader, self, &mut selected)?;
+        Ok(selected.0)
+    }
+
+    fn reached_max(&self, selected: usize) -> bool {
+        self.options.max_count.is_some_and(|max| selected >= max)
+    }
+}
+
+// 按 BOM 或指定编码转码之后的带缓冲的 reader
+pub type Decoded<R> = BufReader<DecodeReaderBytes<R, Vec<u8>>>;
+
+// Searcher::matches 返回的迭代器，Match 借用自原始的 text
+pub struct Matches<'s, 'a> {
+    searcher: &'s Searcher,
+    text: &'a str,
+    // 需要整个输入时一次搜索出来的结果
+    whole: Option<std::vec::IntoIter<OwnedMatch>>,
+    rest: &'a str,
+    line_number: usize,
+    offset: usize,
+    selected: usize,
+}
+
+impl<'a> Iterator for Matches<'_, 'a> {
+    type Item = Match<'a>;
+
+    fn next(&mut self) -> Option<Match<'a>> {
+        if self.searcher.needs_whole_input() {
+            let text = self.text;
+            let searcher = self.searcher;
+            // 输入和 sink 都在内存里，不会出错
+            let whole = self.whole.get_or_insert_with(|| {
+                searcher
+                    .select_all(text.as_bytes())
+                    .unwrap_or_default()
+                    .into_iter()
+            });
+            return whole.next().map(|m| {
+                let line = text[m.byte_offset..].split('\n').next().unwrap_or_default();
+                Match {
+                    line_number: m.line_number,
+                    byte_offset: m.byte_offset,
+                    line: line.strip_suffix('\r').unwrap_or(line),
+                    spans: m.spans,
+                    patterns: m.patterns,
+                }
+            });
+        }
+        while !self.rest.is_empty() && !self.searcher.reached_max(self.selected) {
+            let end = self.rest.find('\n').map_or(self.rest.len(), |i| i + 1);
+            let (raw, rest) = self.rest.split_at(end);
+            self.rest = rest;
+            self.line_number += 1;
+            let offset = self.offset;
+            self.offset += raw.len();
+
+            let line = raw.strip_suffix('\n').unwrap_or(raw);
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            if let Some(m) = self.searcher.select(self.line_number, offset, line) {
+                self.selected += 1;
+                return Some(m);
+            }
+        }
+        None
+    }
+}
+
+// 从 Read 中读到的匹配行；内容不能借用 reader 的缓冲区，所以是自有的 String
+#[derive(Debug, Clone, PartialEq)]
+pub struct OwnedMatch {
+    pub line_number: usize,
+    pub byte_offset: usize,
+    pub line: String,
+    pub spans: Vec<std::ops::Range<usize>>,
+    pub patterns: Vec<usize>,
+}
+
+// Searcher::matches_reader/matches_path 返回的迭代器，读取出错后停止
+pub struct ReaderMatches<'s, R> {
+    searcher: &'s Searcher,
+    reader: R,
+    whole: Option<std::vec::IntoIter<OwnedMatch>>,
+    path: Option<PathBuf>,
+    buf: Vec<u8>,
+    line_number: usize,
+    offset: usize,
+    selected: usize,
+    failed: bool,
+}
+
+impl<'s, R: BufRead> ReaderMatches<'s, R> {
+    fn new(searcher: &'s Searcher, reader: R, path: Option<PathBuf>) -> ReaderMatches<'s, R> {
+        ReaderMatches {
+            searcher,
+            reader,
+            whole: None,
+            path,
+            buf: Vec::new(),
+            line_number: 0,
+            offset: 0,
+            selected: 0,
+            failed: false,
+        }
+    }
+
+    fn error(&mut self, err: io::Error) -> MinigrepError {
+        self.failed = true;
+        match &self.path {
+            Some(path) => MinigrepError::io(path, err),
+            None => MinigrepError::Io {
+                path: None,
+                source: err,
+            },
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for ReaderMatches<'_, R> {
+    type Item = Result<OwnedMatch, MinigrepError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        if self.searcher.needs_whole_input() {
+            if self.whole.is_none() {
+                match self.searcher.select_all(&mut self.reader) {
+                    Ok(selected) => self.whole = Some(selected.into_iter()),
+                    Err(err) => return Some(Err(self.error(err))),
+                }
+            }
+            return self.whole.as_mut()?.next().map(Ok);
+        }
+        while !self.failed && !self.searcher.reached_max(self.selected) {
+            self.buf.clear();
+            let read = match self.reader.read_until(b'\n', &mut self.buf) {
+                Ok(0) => return None,
+                Ok(read) => read,
+                Err(err) => return Some(Err(self.error(err))),
+            };
+            self.line_number += 1;
+            let offset = self.offset;
+            self.offset += read;
+
+            let line = stream::trim_newline(&self.buf);
+            if let Some(m) = self.searcher.select(self.line_number, offset, &line) {
+                self.selected += 1;
+                return Some(Ok(OwnedMatch {
+                    line_number: m.line_number,
+                    byte_offset: m.byte_offset,
+                    line: m.line.to_string(),
+                    spans: m.spans,
+                    patterns: m.patterns,
+                }));
+            }
+        }
+        None
+    }
+}
+
+// select_all 用的 Sink：只收集被选中的行，不关心上下文
+struct Selected(Vec<OwnedMatch>);
+
+impl Sink for Selected {
+    fn matched(&mut self, line: &Line) -> io::Result<bool> {
+        let m = Match::from_hits(line.number, line.offset, line.text, line.hits.to_vec());
+        self.0.push(OwnedMatch {
+            line_number: m.line_number,
+            byte_offset: m.byte_offset,
+            line: m.line.to_string(),
+            spans: m.spans,
+            patterns: m.patterns,
+        });
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POEM: &str =
+        "I'm nobody! Who are you?\nAre you nobody, too?\nThen there's a pair of us - don't tell!\n";
+
+    // 把收到的行记成 "行号:内容"，上下文行用 '-'
+    #[derive(Default)]
+    struct Collect(Vec<String>);
+
+    impl Sink for Collect {
+        fn matched(&mut self, line: &Line) -> io::Result<bool> {
+            self.0.push(format!("{}:{}", line.number, line.text));
+            Ok(true)
+        }
+
+        fn context(&mut self, line: &Line) -> io::Result<bool> {
+            self.0.push(format!("{}-{}", line.number, line.text));
+            Ok(true)
+        }
+
+        fn context_break(&mut self) -> io::Result<()> {
+            self.0.push("--".to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn lazy_matches_over_str_and_reader() {
+        let searcher = SearcherBuilder::new()
+            .pattern("YOU")
+            .ignore_case(true)
+            .max_count(Some(1))
+            .build()
+            .unwrap();
+
+        let mut matches = searcher.matches(POEM);
+        let first = matches.next().unwrap();
+        assert_eq!((1, 0), (first.line_number, first.byte_offset));
+        assert_eq!(Some(&(20..23)), first.spans.first());
+        assert!(matches.next().is_none());
+
+        let searcher = SearcherBuilder::new()
+            .patterns(["nobody", "us"])
+            .build()
+            .unwrap();
+        let lines: Vec<usize> = searcher
+            .matches_reader(POEM.as_bytes())
+            .map(|m| m.unwrap().line_number)
+            .collect();
+        assert_eq!(vec![1, 2, 3], lines);
+    }
+
+    #[test]
+    fn sink_receives_context() {
+        let searcher = SearcherBuilder::new()
+            .pattern(r"\bus\b")
+            .kind(PatternKind::Regex)
+            .before_context(1)
+            .build()
+            .unwrap();
+        let mut sink = Collect::default();
+        let stats = searcher.search_str(POEM, &mut sink).unwrap();
+
+        assert_eq!(1, stats.matched_lines);
+        assert_eq!(
+            vec![
+                "2-Are you nobody, too?",
+                "3:Then there's a pair of us - don't tell!"
+            ],
+            sink.0
+        );
+    }
+
+    #[test]
+    fn multiline_iterators() {
+        let searcher = SearcherBuilder::new()
+            .pattern(r"nobody,\s+too\?\nThen")
+            .kind(PatternKind::Regex)
+            .multiline(true)
+            .build()
+            .unwrap();
+
+        let lines: Vec<String> = searcher
+            .matches(POEM)
+            .map(|m| format!("{}:{}:{:?}", m.line_number, m.line, m.spans))
+            .collect();
+        assert_eq!(
+            vec![
+                "2:Are you nobody, too?:[8..20]",
+                "3:Then there's a pair of us - don't tell!:[0..4]"
+            ],
+            lines
+        );
+
+        let owned: Vec<OwnedMatch> = searcher
+            .matches_reader(POEM.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            vec![(2, 25), (3, 46)],
+            owned
+                .iter()
+                .map(|m| (m.line_number, m.byte_offset))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!("Then there's a pair of us - don't tell!", owned[1].line);
+    }
+
+    #[test]
+    fn not_only_query_iterators_agree_with_sink() {
+        let searcher = SearcherBuilder::new()
+            .pattern("NOT nobody")
+            .kind(PatternKind::Boolean)
+            .build()
+            .unwrap();
+        let mut sink = Collect::default();
+        searcher.search_str(POEM, &mut sink).unwrap();
+
+        let lines: Vec<String> = searcher
+            .matches(POEM)
+            .map(|m| format!("{}:{}", m.line_number, m.line))
+            .collect();
+        assert_eq!(vec!["3:Then there's a pair of us - don't tell!"], lines);
+        assert_eq!(sink.0, lines);
+        assert_eq!(1, searcher.matches_reader(POEM.as_bytes()).count());
+    }
+
+    #[test]
+    fn build_errors() {
+        let err = SearcherBuilder::new()
+            .pattern("(")
+            .kind(PatternKind::Regex)
+            .build();
+        assert!(matches!(err, Err(MinigrepError::Pattern(_))));
+
+        let err = SearcherBuilder::new()
+            .patterns(["a", "b"])
+            .kind(PatternKind::Fuzzy(1))
+            .build();
+        assert!(matches!(err, Err(MinigrepError::Usage(_))));
+    }
+}
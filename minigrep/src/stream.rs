@@ -0,0 +1,747 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::{self, BufRead};
+use std::time::Instant;
+
+use encoding_rs::Encoding;
+use memchr::memmem::Finder;
+
+use crate::output::{Line, Stats};
+use crate::{Hit, Matcher, Scope, Searcher, Sink};
+
+// 行选择相关的选项：-v、-m、-U，以及交给 Sink 之前对匹配行做的替换（--replace）
+#[derive(Debug, Clone, Default)]
+pub struct SearchOptions {
+    pub invert: bool,
+    pub max_count: Option<usize>,
+    pub replace: Option<String>,
+    // -a：即使内容里有 NUL 字节也当作文本输出
+    pub text: bool,
+    // 读取输入时使用的编码，None 表示根据 BOM 自动识别
+    pub encoding: Option<&'static Encoding>,
+    // -U：在整个输入上匹配，匹配可以跨越换行符
+    pub multiline: bool,
+    // --scope paragraph：模式对整个段落求值
+    pub scope: Scope,
+}
+
+// 逐行读取 reader 并把选中的行和上下文交给 sink，返回这个输入的统计信息
+// 任何时候内存里只保存当前行和 -B 需要的前几行，所以可以处理任意大小的输入
+// -U 是例外：匹配可以跨行，只能把整个输入读进内存
+pub(crate) fn search_reader<R: BufRead, S: Sink>(
+    mut reader: R,
+    searcher: &Searcher,
+    sink: &mut S,
+) -> io::Result<Stats> {
+    let start = Instant::now();
+    let matcher = searcher.matcher();
+    let options = searcher.options();
+    if options.scope == Scope::Paragraph {
+        return search_paragraphs(reader, searcher, sink, start);
+    }
+    if !options.multiline {
+        return search_lines(reader, searcher, sink, false);
+    }
+
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    // 匹配在解码后的整段文本上进行；scan 交给 select 的位置也是解码后文本里的位置
+    let text = String::from_utf8_lossy(&bytes);
+    let mut hits = matcher.find_hits(&text);
+    // -U 时 -m 限制的是匹配的个数，这样最后一处匹配覆盖的行也能完整输出
+    let max_lines = if options.invert {
+        options.max_count
+    } else {
+        hits.truncate(options.max_count.unwrap_or(usize::MAX));
+        None
+    };
+    let mut first = 0;
+    scan(
+        &bytes[..],
+        searcher,
+        sink,
+        start,
+        max_lines,
+        ScanHints::default(),
+        |offset, read, line| {
+            let end = offset + read;
+            // 跳过在这一行之前就已经结束的匹配；匹配按位置排好序且互不重叠
+            while hits
+                .get(first)
+                .is_some_and(|hit| hit.range.start < offset && hit.range.end <= offset)
+            {
+                first += 1;
+            }
+            let covering = hits[first..].iter().take_while(|hit| hit.range.start < end);
+            let line_end = offset + line.len();
+            let mut selection = Selection {
+                hits: Vec::new(),
+                matches: 0,
+            };
+            for hit in covering {
+                // 把匹配裁剪到这一行（不含换行符）的范围内，再换算成行内位置
+                let from = hit.range.start.clamp(offset, line_end);
+                let to = hit.range.end.clamp(from, line_end);
+                selection.hits.push(Hit {
+                    range: from - offset..to - offset,
+                    pattern: hit.pattern,
+                });
+                if hit.range.start >= offset {
+                    selection.matches += 1;
+                }
+            }
+            let covered = !selection.hits.is_empty();
+            if covered == options.invert {
+                None
+            } else if options.invert {
+                Some(Selection {
+                    hits: Vec::new(),
+                    matches: 0,
+                })
+            } else {
+                Some(selection)
+            }
+        },
+    )
+}
+
+// 逐行匹配，每行单独判断；binary 为 true 表示这段输入之前的数据里已经出现过 NUL
+// （--follow 每次只把新增的完整行交给这里）
+pub(crate) fn search_lines<R: BufRead, S: Sink>(
+    reader: R,
+    searcher: &Searcher,
+    sink: &mut S,
+    binary: bool,
+) -> io::Result<Stats> {
+    let start = Instant::now();
+    let matcher = searcher.matcher();
+    let options = searcher.options();
+    let invert = options.invert;
+    let hints = ScanHints {
+        binary,
+        // -v 选中的恰好是不包含字面量的行，不能跳过
+        prefilter: matcher.required_literal().filter(|_| !invert),
+    };
+    scan(
+        reader,
+        searcher,
+        sink,
+        start,
+        options.max_count,
+        hints,
+        |_, _, line| {
+            if matcher.is_match(line) == invert {
+                return None;
+            }
+            // -v 选出的行没有可以高亮的匹配
+            let hits = if invert {
+                Vec::new()
+            } else {
+                matcher.find_hits(line)
+            };
+            let matches = hits.len();
+            Some(Selection { hits, matches })
+        },
+    )
+}
+
+// --scope paragraph：连续的非空行组成一个段落，把段落里的行用换行符连起来交给模式判断
+// 段落满足模式（-v 时不满足）就选中其中的每一行；空行永远不会被选中
+fn search_paragraphs<R: BufRead, S: Sink>(
+    reader: R,
+    searcher: &Searcher,
+    sink: &mut S,
+    start: Instant,
+) -> io::Result<Stats> {
+    let matcher = searcher.matcher();
+    let options = searcher.options();
+    let verdicts = RefCell::new(VecDeque::new());
+    let reader = Paragraphs {
+        inner: reader,
+        matcher,
+        buf: Vec::new(),
+        pos: 0,
+        verdicts: &verdicts,
+    };
+    scan(
+        reader,
+        searcher,
+        sink,
+        start,
+        options.max_count,
+        ScanHints::default(),
+        |_, _, line| {
+            // Paragraphs 在交出一个段落的字节之前就已经放好了它的结果，scan 按顺序逐行取
+            let verdict = {
+                let mut verdicts = verdicts.borrow_mut();
+                let run = verdicts.front_mut()?;
+                run.0 -= 1;
+                let verdict = run.1;
+                if run.0 == 0 {
+                    verdicts.pop_front();
+                }
+                verdict
+            };
+            if verdict.is_none_or(|is_match| is_match == options.invert) {
+                return None;
+            }
+            let hits = if options.invert {
+                Vec::new()
+            } else {
+                matcher.highlights(line)
+            };
+            let matches = hits.len();
+            Some(Selection { hits, matches })
+        },
+    )
+}
+
+// --scope paragraph 用的 reader：每次从 inner 读出一个完整的段落（连同结束它的空行）再交给 scan，
+// 内存里只有当前这一个段落；交出之前先判断段落是否满足模式，
+// 把 (行数, 结果) 放进 verdicts，空行的结果是 None，永远不会被选中
+struct Paragraphs<'a, R> {
+    inner: R,
+    matcher: &'a Matcher,
+    buf: Vec<u8>,
+    pos: usize,
+    verdicts: &'a RefCell<VecDeque<(usize, Option<bool>)>>,
+}
+
+impl<R: BufRead> Paragraphs<'_, R> {
+    fn next_paragraph(&mut self) -> io::Result<()> {
+        self.buf.clear();
+        self.pos = 0;
+        // 段落里的行去掉换行符后用 \n 连起来交给模式判断
+        let mut text = String::new();
+        let mut lines = 0;
+        let mut blank = false;
+        loop {
+            let start = self.buf.len();
+            if self.inner.read_until(b'\n', &mut self.buf)? == 0 {
+                break;
+            }
+            let line = trim_newline(&self.buf[start..]);
+            if line.trim().is_empty() {
+                blank = true;
+                break;
+            }
+            if lines > 0 {
+                text.push('\n');
+            }
+            text.push_str(&line);
+            lines += 1;
+        }
+        let mut verdicts = self.verdicts.borrow_mut();
+        if lines > 0 {
+            verdicts.push_back((lines, Some(self.matcher.is_match(&text))));
+        }
+        if blank {
+            verdicts.push_back((1, None));
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead> io::Read for Paragraphs<'_, R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(out.len());
+        out[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for Paragraphs<'_, R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos == self.buf.len() {
+            self.next_paragraph()?;
+        }
+        Ok(&self.buf[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos += amt;
+    }
+}
+
+// 和 grep 一样用 NUL 字节判断二进制数据；-a 时调用方不做这个检查
+pub(crate) fn looks_binary(bytes: &[u8]) -> bool {
+    bytes.contains(&0)
+}
+
+// 被选中的一行：行内要高亮的位置，以及从这一行开始的匹配个数
+// -U 时一处匹配可能覆盖好几行，但只在它开始的那一行计数
+struct Selection {
+    hits: Vec<Hit>,
+    matches: usize,
+}
+
+// scan 开始之前就知道的事情
+#[derive(Default)]
+struct ScanHints<'f> {
+    // 之前的数据里出现过 NUL（--follow 分批交来的内容），一开始就当作二进制输入
+    binary: bool,
+    // 被选中的行一定包含这个字面量：在整块缓冲区里查找它，跳过前面所有不可能被选中的行，
+    // 不用逐行调用 select
+    prefilter: Option<&'f Finder<'static>>,
+}
+
+// 按行扫描 reader；select 根据 (行在解码后文本里的偏移, 解码后包括换行符的长度, 行内容)
+// 决定是否选中这一行；交给 sink 的 offset 是 reader 里的字节偏移：不转码的输入就是文件里的位置，
+// 有 BOM 或者指定了 --encoding 时 reader 已经转成了 UTF-8，是转码之后文本里的位置
+// max_lines 为 Some 时选中这么多行之后只再输出剩余的后文
+fn scan<R, S, F>(
+    mut reader: R,
+    searcher: &Searcher,
+    sink: &mut S,
+    start: Instant,
+    max_lines: Option<usize>,
+    hints: ScanHints,
+    mut select: F,
+) -> io::Result<Stats>
+where
+    R: BufRead,
+    S: Sink,
+    F: FnMut(usize, usize, &str) -> Option<Selection>,
+{
+    let matcher = searcher.matcher();
+    let options = searcher.options();
+    sink.begin()?;
+    let before = searcher.before_context();
+    let mut lines = Lines {
+        sink,
+        has_context: before > 0 || searcher.after_context() > 0,
+        last_printed: None,
+        stopped: false,
+    };
+    // 最近的 before 行放在环形队列里，after_remaining 记录还要输出几行后文
+    let mut before_lines: VecDeque<(usize, usize, Vec<u8>)> = VecDeque::with_capacity(before);
+    let mut after_remaining = 0;
+    let mut buf = Vec::new();
+    let mut line_number = 0;
+    let mut offset = 0;
+    let mut text_offset = 0;
+    // 和 grep 一样用 NUL 字节判断二进制文件：先看第一块缓冲区，之后每一行也会检查
+    let mut binary = hints.binary || (!options.text && looks_binary(reader.fill_buf()?));
+    let mut stats = Stats {
+        searches: 1,
+        ..Stats::default()
+    };
+
+    while !lines.stopped {
+        let skipping =
+            after_remaining == 0 && max_lines.is_none_or(|max| stats.matched_lines < max);
+        if let Some(finder) = hints.prefilter.filter(|_| skipping) {
+            let available = reader.fill_buf()?;
+            // 字面量不含换行符时一定落在某一行里面：第一处出现之前的完整行都可以跳过；
+            // 缓冲区里找不到时跳过所有完整的行，最后的半行留给下一块
+            let limit = match finder.find(available) {
+                Some(hit) => &available[..hit],
+                None => available,
+            };
+            let limit = memchr::memrchr(b'\n', limit).map_or(0, |i| i + 1);
+            if limit > 0 {
+                let skipped = &available[..limit];
+                let count = memchr::memchr_iter(b'\n', skipped).count();
+                binary = binary || (!options.text && looks_binary(skipped));
+                // -B 需要被跳过的最后几行
+                let mut end = limit;
+                let mut tail = Vec::new();
+                while tail.len() < before && end > 0 {
+                    let line_start =
+                        memchr::memrchr(b'\n', &skipped[..end - 1]).map_or(0, |i| i + 1);
+                    tail.push((line_start, end));
+                    end = line_start;
+                }
+                for (i, &(line_start, line_end)) in tail.iter().enumerate().rev() {
+                    if before_lines.len() == before {
+                        before_lines.pop_front();
+                    }
+                    let raw = trim_newline_bytes(&skipped[line_start..line_end]);
+                    before_lines.push_back((
+                        line_number + count - i,
+                        offset + line_start,
+                        raw.to_vec(),
+                    ));
+                }
+                text_offset += match std::str::from_utf8(skipped) {
+                    Ok(text) => text.len(),
+                    Err(_) => String::from_utf8_lossy(skipped).len(),
+                };
+                line_number += count;
+                offset += limit;
+                reader.consume(limit);
+            }
+        }
+
+        buf.clear();
+        let read = reader.read_until(b'\n', &mut buf)?;
+        if read == 0 {
+            break;
+        }
+        line_number += 1;
+        let line_offset = offset;
+        offset += read;
+        binary = binary || (!options.text && looks_binary(&buf));
+        let raw = trim_newline_bytes(&buf);
+        let line = String::from_utf8_lossy(raw);
+        let line = line.as_ref();
+        let text_start = text_offset;
+        let text_read = line.len() + (buf.len() - raw.len());
+        text_offset += text_read;
+
+        if max_lines.is_some_and(|max| stats.matched_lines >= max) {
+            // 达到 -m 上限之后只把剩余的后文输出完
+            if after_remaining > 0 {
+                lines.context(line_number, line_offset, line, raw)?;
+                after_remaining -= 1;
+                continue;
+            }
+            break;
+        }
+
+        if let Some(Selection { hits, matches }) = select(text_start, text_read, line) {
+            stats.matched_lines += 1;
+            stats.matches += matches;
+            // 替换之后原来的匹配位置已经不对了，不再高亮
+            let replaced;
+            let (text, bytes, spans) = match &options.replace {
+                Some(replacement) => {
+                    replaced = matcher.replace(line, replacement);
+                    (replaced.as_str(), replaced.as_bytes(), &[][..])
+                }
+                None => (line, raw, &hits[..]),
+            };
+            let selected = Line {
+                number: line_number,
+                offset: line_offset,
+                text,
+                bytes,
+                hits: spans,
+                is_match: true,
+                distance: matcher.distance(line),
+            };
+            // 二进制输入不输出前文，由 sink 决定怎么提示
+            if binary {
+                lines.stopped = !lines.sink.binary_match(&selected)?;
+                continue;
+            }
+            let first = before_lines.front().map_or(line_number, |l| l.0);
+            lines.separate(first)?;
+            for (n, o, raw) in before_lines.drain(..) {
+                lines.context(n, o, &String::from_utf8_lossy(&raw), &raw)?;
+            }
+            lines.matched(&selected)?;
+            after_remaining = searcher.after_context();
+        } else if after_remaining > 0 {
+            lines.context(line_number, line_offset, line, raw)?;
+            after_remaining -= 1;
+        } else if before > 0 {
+            if before_lines.len() == before {
+                before_lines.pop_front();
+            }
+            before_lines.push_back((line_number, line_offset, raw.to_vec()));
+        }
+    }
+
+    stats.bytes_searched = offset;
+    if stats.matched_lines > 0 {
+        stats.searches_with_match = 1;
+    }
+    stats.elapsed = start.elapsed();
+    lines.sink.finish(&stats)?;
+    Ok(stats)
+}
+
+// 去掉行尾的 \n 或 \r\n，和 str::lines 的规则一致
+// 不合法的 UTF-8 字节替换成 U+FFFD 继续搜索，合法时不会分配新的 String
+pub(crate) fn trim_newline(buf: &[u8]) -> Cow<'_, str> {
+    String::from_utf8_lossy(trim_newline_bytes(buf))
+}
+
+pub(crate) fn trim_newline_bytes(buf: &[u8]) -> &[u8] {
+    let bytes = buf.strip_suffix(b"\n").unwrap_or(buf);
+    bytes.strip_suffix(b"\r").unwrap_or(bytes)
+}
+
+// 记住最后交给 sink 的行号，用来判断两个片段之间是否需要分隔
+struct Lines<'a, S> {
+    sink: &'a mut S,
+    has_context: bool,
+    last_printed: Option<usize>,
+    // sink 表示不再需要后面的内容
+    stopped: bool,
+}
+
+impl<S: Sink> Lines<'_, S> {
+    // 即将从 first 行开始输出新片段，和上一段不相邻时先通知 sink
+    fn separate(&mut self, first: usize) -> io::Result<()> {
+        match self.last_printed {
+            Some(last) if self.has_context && first > last + 1 => self.sink.context_break(),
+            _ => Ok(()),
+        }
+    }
+
+    fn matched(&mut self, line: &Line) -> io::Result<()> {
+        self.last_printed = Some(line.number);
+        self.stopped = self.stopped || !self.sink.matched(line)?;
+        Ok(())
+    }
+
+    fn context(
+        &mut self,
+        number: usize,
+        offset: usize,
+        text: &str,
+        bytes: &[u8],
+    ) -> io::Result<()> {
+        self.last_printed = Some(number);
+        let line = Line {
+            number,
+            offset,
+            text,
+            bytes,
+            hits: &[],
+            is_match: false,
+            distance: None,
+        };
+        self.stopped = self.stopped || !self.sink.context(&line)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::{Printer, PrinterSink};
+    use crate::{searcher_builder, Config, OutputMode, SearcherBuilder, SortMode};
+    use std::path::Path;
+
+    fn run(contents: &str, config: &Config) -> String {
+        let builder = searcher_builder(config, vec![config.query.clone()]);
+        run_with(contents, config, builder)
+    }
+
+    fn run_with(contents: &str, config: &Config, builder: SearcherBuilder) -> String {
+        let searcher = builder.build().unwrap();
+        let printer = Printer::new(config, false);
+        let mut out = Vec::new();
+        let mut sink = PrinterSink::new(&printer, Path::new("-"), &mut out);
+        search_reader(contents.as_bytes(), &searcher, &mut sink).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn merges_overlapping_context() {
+        let config = Config {
+            query: "x".to_string(),
+            line_number: true,
+            before_context: 1,
+            after_context: 1,
+            ..Config::default()
+        };
+        let contents = "a\nx1\nb\nx2\nc\nd\ne\nx3\n";
+
+        assert_eq!(
+            "1-a\n2:x1\n3-b\n4:x2\n5-c\n--\n7-e\n8:x3\n",
+            run(contents, &config)
+        );
+    }
+
+    #[test]
+    fn max_count_keeps_trailing_context() {
+        let config = Config {
+            query: "x".to_string(),
+            after_context: 1,
+            max_count: Some(1),
+            ..Config::default()
+        };
+
+        assert_eq!("x1\na\n", run("x1\na\nx2\nb\n", &config));
+    }
+
+    #[test]
+    fn replaces_matched_lines() {
+        let config = Config {
+            query: "you".to_string(),
+            replace: Some("we".to_string()),
+            ..Config::default()
+        };
+
+        assert_eq!("who are we?\n", run("who are you?\nnobody\n", &config));
+    }
+
+    #[test]
+    fn binary_and_invalid_utf8() {
+        let config = Config {
+            query: "you".to_string(),
+            ..Config::default()
+        };
+
+        assert_eq!(
+            "Binary file (standard input) matches\n",
+            run("\0\nyou\nyou\n", &config)
+        );
+
+        let searcher = SearcherBuilder::new().pattern("caf").build().unwrap();
+        let printer = Printer::new(&config, false);
+        let mut out = Vec::new();
+        let input: &[u8] = b"caf\xe9 au lait\nthe\n";
+        let mut sink = PrinterSink::new(&printer, Path::new("-"), &mut out);
+        search_reader(input, &searcher, &mut sink).unwrap();
+        assert_eq!("caf\u{fffd} au lait\n", String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn json_events() {
+        let config = Config {
+            query: "you".to_string(),
+            output: OutputMode::Json,
+            ..Config::default()
+        };
+        let out = run("who are you?\nnobody\n", &config);
+        let events: Vec<&str> = out.lines().collect();
+
+        assert_eq!(3, events.len());
+        assert_eq!(
+            r#"{"type":"begin","data":{"path":{"text":"<stdin>"}}}"#,
+            events[0]
+        );
+        assert_eq!(
+            r#"{"type":"match","data":{"path":{"text":"<stdin>"},"lines":{"text":"who are you?"},"line_number":1,"absolute_offset":0,"submatches":[{"match":{"text":"you"},"start":8,"end":11,"pattern":0}]}}"#,
+            events[1]
+        );
+        assert!(events[2].starts_with(r#"{"type":"end","#));
+        assert!(events[2].contains(r#""bytes_searched":20,"matched_lines":1,"matches":1}"#));
+    }
+
+    #[test]
+    fn literal_prefilter_agrees_with_regex() {
+        // 正则不走预过滤，逐行匹配的结果作为对照
+        let contents = "a\nb\nc needle\nd\ncaf\u{e9}\nneedle e\r\nf\ng\nh\nneedle\nneedle\ni";
+        for (before, after) in [(0, 0), (1, 0), (2, 1), (0, 2)] {
+            let config = |regex| Config {
+                query: "needle".to_string(),
+                regex,
+                line_number: true,
+                byte_offset: true,
+                before_context: before,
+                after_context: after,
+                ..Config::default()
+            };
+            let literal = run(contents, &config(false));
+            let regex = run(contents, &config(true));
+            assert_eq!(regex, literal, "-B{before} -A{after}");
+        }
+    }
+
+    #[test]
+    fn multiline_matches_cover_lines() {
+        let mut config = Config {
+            query: r"fn open\(\s*path".to_string(),
+            regex: true,
+            multiline: true,
+            line_number: true,
+            after_context: 1,
+            ..Config::default()
+        };
+        let input = "use std::fs;\npub fn open(\n    path: &str,\n) {}\nfn open(path) {}\n";
+
+        assert_eq!(
+            "2:pub fn open(\n3:    path: &str,\n4-) {}\n5:fn open(path) {}\n",
+            run(input, &config)
+        );
+
+        // -m 数的是匹配：第一处匹配覆盖的两行都要输出
+        config.max_count = Some(1);
+        config.after_context = 0;
+        assert_eq!("2:pub fn open(\n3:    path: &str,\n", run(input, &config));
+
+        config.max_count = None;
+        config.invert = true;
+        assert_eq!("1:use std::fs;\n4:) {}\n", run(input, &config));
+    }
+
+    #[test]
+    fn boolean_query_per_paragraph() {
+        let mut config = Config {
+            query: "timeout AND retry AND NOT debug".to_string(),
+            boolean: true,
+            line_number: true,
+            ..Config::default()
+        };
+        let input = "request timeout\nwill retry\n\ndebug: timeout\nretry\n\ntimeout, retry\n";
+
+        assert_eq!("7:timeout, retry\n", run(input, &config));
+
+        config.scope = Scope::Paragraph;
+        assert_eq!(
+            "1:request timeout\n2:will retry\n7:timeout, retry\n",
+            run(input, &config)
+        );
+
+        config.invert = true;
+        assert_eq!("4:debug: timeout\n5:retry\n", run(input, &config));
+    }
+
+    #[test]
+    fn paragraphs_are_read_one_at_a_time() {
+        // 读到第一个段落之后的内容就报错：整个输入读进内存的实现会失败
+        struct Unreadable;
+        impl io::Read for Unreadable {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::other("read past the first paragraph"))
+            }
+        }
+
+        let config = Config {
+            query: "timeout AND retry".to_string(),
+            boolean: true,
+            line_number: true,
+            scope: Scope::Paragraph,
+            max_count: Some(1),
+            ..Config::default()
+        };
+        let searcher = searcher_builder(&config, vec![config.query.clone()])
+            .build()
+            .unwrap();
+        let printer = Printer::new(&config, false);
+        let mut out = Vec::new();
+        let mut sink = PrinterSink::new(&printer, Path::new("-"), &mut out);
+        let input = io::BufReader::new(io::Read::chain(
+            &b"request timeout\nwill retry\n\n"[..],
+            Unreadable,
+        ));
+        search_reader(input, &searcher, &mut sink).unwrap();
+        assert_eq!("1:request timeout\n", String::from_utf8(out).unwrap());
+
+        // 连续的空行、\r\n 和最后没有换行符的段落
+        let config = Config {
+            max_count: None,
+            ..config
+        };
+        assert_eq!(
+            "3:timeout\n4:retry\n",
+            run("\r\n \r\ntimeout\r\nretry", &config)
+        );
+    }
+
+    #[test]
+    fn ranks_fuzzy_matches_by_distance() {
+        let config = Config {
+            query: "timeout".to_string(),
+            line_number: true,
+            fuzzy: Some(2),
+            sort: SortMode::Distance,
+            ..Config::default()
+        };
+        let input = "tmieout 1\nok\ntimeuot 2\ntimeout 3\n";
+
+        assert_eq!(
+            "4:0:timeout 3\n1:2:tmieout 1\n3:2:timeuot 2\n",
+            run(input, &config)
+        );
+    }
+}
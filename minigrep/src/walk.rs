@@ -0,0 +1,86 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use ignore::overrides::OverrideBuilder;
+use ignore::WalkBuilder;
+
+use crate::MinigrepError;
+
+// 把命令行给出的文件和目录展开成要搜索的文件列表
+// 目录会被递归遍历，并遵守 .gitignore/.ignore 规则以及 --include/--exclude
+// 遍历中的错误（没有权限的子目录、坏掉的符号链接等）不会中断遍历，和文件列表一起返回，
+// 由调用方报告；只有 glob 写错才直接返回 Err
+pub fn collect_files(
+    paths: &[String],
+    include: &[String],
+    exclude: &[String],
+) -> Result<(Vec<PathBuf>, Vec<MinigrepError>), MinigrepError> {
+    let mut files = Vec::new();
+    let mut errors = Vec::new();
+
+    for path in paths {
+        let root = Path::new(path);
+        // 显式给出的文件总是会被搜索，不受 glob 过滤影响
+        if !root.is_dir() {
+            files.push(root.to_path_buf());
+            continue;
+        }
+
+        // ignore 的 override 规则：普通 glob 是白名单，以 ! 开头的是黑名单
+        let mut overrides = OverrideBuilder::new(root);
+        let glob_error = |err: ignore::Error| MinigrepError::Pattern(Box::new(err));
+        for glob in include {
+            overrides.add(glob).map_err(glob_error)?;
+        }
+        for glob in exclude {
+            overrides.add(&format!("!{glob}")).map_err(glob_error)?;
+        }
+
+        let walker = WalkBuilder::new(root)
+            // 不在 git 仓库里时也读取 .gitignore
+            .require_git(false)
+            .overrides(overrides.build().map_err(glob_error)?)
+            .build();
+        for entry in walker {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(err) => {
+                    errors.push(MinigrepError::io(root, io::Error::other(err)));
+                    continue;
+                }
+            };
+            if entry.file_type().is_some_and(|t| t.is_file()) {
+                files.push(entry.into_path());
+            }
+        }
+    }
+
+    Ok((files, errors))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn include_and_exclude() {
+        let root = std::env::temp_dir().join(format!("minigrep-walk-{}", std::process::id()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join("target")).unwrap();
+        fs::write(root.join("src/lib.rs"), "fn main() {}").unwrap();
+        fs::write(root.join("src/notes.txt"), "notes").unwrap();
+        fs::write(root.join("target/out.rs"), "fn out() {}").unwrap();
+
+        let (files, errors) = collect_files(
+            &[root.to_string_lossy().into_owned()],
+            &["*.rs".to_string()],
+            &["target/".to_string()],
+        )
+        .unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(vec![root.join("src/lib.rs")], files);
+        assert!(errors.is_empty());
+    }
+}
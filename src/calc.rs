@@ -0,0 +1,413 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+// 整数表达式计算器，例如
+//   let x = 10
+//   (x + 20) * -3 % 7
+// 支持 + - * / %、括号、一元负号和 let 定义的变量；/ 和 % 与 Rust 一样向 0 取整
+// 所有运算都用 checked_* 计算，溢出报告为错误而不会回绕
+#[derive(Default)]
+pub struct Calculator {
+    variables: HashMap<String, i64>,
+}
+
+// 词法、语法或者计算错误；position 是错误在输入里的字节位置
+#[derive(Debug, PartialEq)]
+pub struct CalcError {
+    input: String,
+    position: usize,
+    message: String,
+}
+
+impl fmt::Display for CalcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let column = self.input[..self.position].chars().count();
+        writeln!(f, "{}", self.message)?;
+        writeln!(f, "  {}", self.input)?;
+        write!(f, "  {}^", " ".repeat(column))
+    }
+}
+
+impl Error for CalcError {}
+
+impl Calculator {
+    pub fn new() -> Calculator {
+        Calculator::default()
+    }
+
+    // 计算一行输入：表达式或者 let 绑定，两者都返回得到的值；空行返回 None
+    // 出错时变量保持不变
+    pub fn eval_line(&mut self, input: &str) -> Result<Option<i64>, CalcError> {
+        let tokens = tokenize(input)?;
+        if tokens.is_empty() {
+            return Ok(None);
+        }
+        let mut parser = Parser {
+            input,
+            tokens,
+            pos: 0,
+        };
+        let (name, expr) = parser.statement()?;
+        let value = self
+            .eval(&expr)
+            .map_err(|(position, message)| error(input, position, &message))?;
+        if let Some(name) = name {
+            self.variables.insert(name, value);
+        }
+        Ok(Some(value))
+    }
+
+    pub fn variable(&self, name: &str) -> Option<i64> {
+        self.variables.get(name).copied()
+    }
+
+    fn eval(&self, expr: &Expr) -> Result<i64, (usize, String)> {
+        match expr {
+            Expr::Number(n) => Ok(*n),
+            Expr::Variable { name, at } => self
+                .variable(name)
+                .ok_or_else(|| (*at, format!("unknown variable '{name}'"))),
+            Expr::Neg { at, operand } => {
+                let value = self.eval(operand)?;
+                value
+                    .checked_neg()
+                    .ok_or_else(|| (*at, format!("-({value}) overflows")))
+            }
+            Expr::Binary { op, at, lhs, rhs } => {
+                let (lhs, rhs) = (self.eval(lhs)?, self.eval(rhs)?);
+                if matches!(op, '/' | '%') && rhs == 0 {
+                    return Err((*at, "division by zero".to_string()));
+                }
+                let result = match op {
+                    '+' => lhs.checked_add(rhs),
+                    '-' => lhs.checked_sub(rhs),
+                    '*' => lhs.checked_mul(rhs),
+                    '/' => lhs.checked_div(rhs),
+                    '%' => lhs.checked_rem(rhs),
+                    _ => unreachable!("tokenize only produces + - * / %"),
+                };
+                result.ok_or_else(|| (*at, format!("{lhs} {op} {rhs} overflows")))
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Number(i64),
+    Ident(String),
+    Let,
+    Assign,
+    Op(char),
+    Open,
+    Close,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "{n}"),
+            Token::Ident(name) => write!(f, "{name}"),
+            Token::Let => write!(f, "let"),
+            Token::Assign => write!(f, "="),
+            Token::Op(op) => write!(f, "{op}"),
+            Token::Open => write!(f, "("),
+            Token::Close => write!(f, ")"),
+        }
+    }
+}
+
+// 把输入切成 (位置, 词法单元)
+fn tokenize(input: &str) -> Result<Vec<(usize, Token)>, CalcError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let token = match c {
+            '+' | '-' | '*' | '/' | '%' => Token::Op(c),
+            '=' => Token::Assign,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '0'..='9' | '_' | 'a'..='z' | 'A'..='Z' => {
+                let mut end = start;
+                while let Some(&(i, c)) = chars.peek() {
+                    if !c.is_ascii_alphanumeric() && c != '_' {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                let word = &input[start..end];
+                let token = if c.is_ascii_digit() {
+                    // 和 Rust 一样允许 1_000 这样的写法
+                    let digits = word.replace('_', "");
+                    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+                        return Err(error(input, start, &format!("invalid number '{word}'")));
+                    }
+                    let n = digits.parse().map_err(|_| {
+                        error(input, start, &format!("{word} doesn't fit in 64 bits"))
+                    })?;
+                    Token::Number(n)
+                } else if word == "let" {
+                    Token::Let
+                } else {
+                    Token::Ident(word.to_string())
+                };
+                tokens.push((start, token));
+                continue;
+            }
+            _ => return Err(error(input, start, &format!("unexpected character '{c}'"))),
+        };
+        chars.next();
+        tokens.push((start, token));
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug)]
+enum Expr {
+    Number(i64),
+    Variable {
+        name: String,
+        at: usize,
+    },
+    Neg {
+        at: usize,
+        operand: Box<Expr>,
+    },
+    Binary {
+        op: char,
+        at: usize,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+}
+
+// 一元负号比所有二元运算符结合得都紧：-2 * 3 是 (-2) * 3
+const PREFIX_POWER: u8 = 5;
+
+// 表达式的最大嵌套深度：解析、计算和释放表达式树都是递归的，
+// 100000 个 ( 或者 - 这样的输入不能把栈用完
+const MAX_DEPTH: usize = 256;
+
+// Pratt 解析的结合力：(左, 右)，右边更大表示左结合
+fn infix_power(op: char) -> (u8, u8) {
+    match op {
+        '+' | '-' => (1, 2),
+        _ => (3, 4),
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    // statement := "let" IDENT "=" expr | expr
+    fn statement(&mut self) -> Result<(Option<String>, Expr), CalcError> {
+        let mut name = None;
+        if self.peek() == Some(&Token::Let) {
+            self.pos += 1;
+            match self.next() {
+                Some((_, Token::Ident(ident))) => name = Some(ident),
+                Some((at, token)) => {
+                    return Err(
+                        self.error(at, &format!("expected a variable name, found '{token}'"))
+                    )
+                }
+                None => return Err(self.error(self.input.len(), "expected a variable name")),
+            }
+            match self.next() {
+                Some((_, Token::Assign)) => {}
+                Some((at, token)) => {
+                    return Err(self.error(at, &format!("expected '=', found '{token}'")))
+                }
+                None => return Err(self.error(self.input.len(), "expected '='")),
+            }
+        }
+        let (expr, _) = self.expr(0, 0)?;
+        match self.next() {
+            Some((at, Token::Close)) => Err(self.error(at, "unmatched ')'")),
+            Some((at, token)) => Err(self.error(at, &format!("unexpected '{token}'"))),
+            None => Ok((name, expr)),
+        }
+    }
+
+    // depth 是递归的层数，返回值里的 usize 是表达式树的高度；
+    // 1 + 1 + ... 在循环里向左生长，不增加递归层数但会增加树高，所以两个都要限制
+    fn expr(&mut self, min_power: u8, depth: usize) -> Result<(Expr, usize), CalcError> {
+        let too_deep = |parser: &Parser, at| parser.error(at, "expression nested too deeply");
+        if depth >= MAX_DEPTH {
+            let at = self.tokens.get(self.pos).map_or(self.input.len(), |t| t.0);
+            return Err(too_deep(self, at));
+        }
+        let (mut lhs, mut height) = match self.next() {
+            Some((_, Token::Number(n))) => (Expr::Number(n), 1),
+            Some((at, Token::Ident(name))) => (Expr::Variable { name, at }, 1),
+            Some((at, Token::Op('-'))) => {
+                let (operand, height) = self.expr(PREFIX_POWER, depth + 1)?;
+                if height >= MAX_DEPTH {
+                    return Err(too_deep(self, at));
+                }
+                let neg = Expr::Neg {
+                    at,
+                    operand: Box::new(operand),
+                };
+                (neg, height + 1)
+            }
+            Some((at, Token::Open)) => {
+                let inner = self.expr(0, depth + 1)?;
+                if self.next().is_none_or(|(_, token)| token != Token::Close) {
+                    return Err(self.error(at, "unclosed '('"));
+                }
+                inner
+            }
+            Some((at, token)) => {
+                return Err(self.error(at, &format!("expected an expression, found '{token}'")))
+            }
+            None => return Err(self.error(self.input.len(), "expected an expression")),
+        };
+        while let Some(&(at, Token::Op(op))) = self.tokens.get(self.pos) {
+            let (left, right) = infix_power(op);
+            if left < min_power {
+                break;
+            }
+            self.pos += 1;
+            let (rhs, rhs_height) = self.expr(right, depth + 1)?;
+            height = height.max(rhs_height) + 1;
+            if height > MAX_DEPTH {
+                return Err(too_deep(self, at));
+            }
+            lhs = Expr::Binary {
+                op,
+                at,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            };
+        }
+        Ok((lhs, height))
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, token)| token)
+    }
+
+    fn next(&mut self) -> Option<(usize, Token)> {
+        if self.pos >= self.tokens.len() {
+            return None;
+        }
+        // 解析过的词法单元不会再用到，直接取走
+        let (at, token) = std::mem::replace(&mut self.tokens[self.pos], (0, Token::Close));
+        self.pos += 1;
+        Some((at, token))
+    }
+
+    fn error(&self, position: usize, message: &str) -> CalcError {
+        error(self.input, position, message)
+    }
+}
+
+fn error(input: &str, position: usize, message: &str) -> CalcError {
+    CalcError {
+        input: input.to_string(),
+        position,
+        message: message.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(input: &str) -> Result<Option<i64>, CalcError> {
+        Calculator::new().eval_line(input)
+    }
+
+    fn message(input: &str) -> String {
+        eval(input).unwrap_err().message
+    }
+
+    #[test]
+    fn precedence_and_associativity() {
+        assert_eq!(Ok(Some(90)), eval("(10 + 20) + (30 + 30_0 / 10)"));
+        assert_eq!(Ok(Some(7)), eval("1 + 2 * 3"));
+        assert_eq!(Ok(Some(9)), eval("(1 + 2) * 3"));
+        assert_eq!(Ok(Some(-4)), eval("1 - 2 - 3"));
+        assert_eq!(Ok(Some(2)), eval("100 / 10 / 5"));
+        assert_eq!(Ok(Some(-6)), eval("-2 * 3"));
+        assert_eq!(Ok(Some(5)), eval("2 - -3"));
+        assert_eq!(Ok(Some(-1)), eval("-7 % 3"));
+        assert_eq!(Ok(None), eval("   "));
+    }
+
+    #[test]
+    fn let_binds_variables() {
+        let mut calc = Calculator::new();
+        assert_eq!(Ok(Some(10)), calc.eval_line("let a = 10"));
+        assert_eq!(Ok(Some(30)), calc.eval_line("let b = a * 3"));
+        assert_eq!(Ok(Some(40)), calc.eval_line("a + b"));
+        assert_eq!(Ok(Some(11)), calc.eval_line("let a = a + 1"));
+        // 出错的 let 不会改变变量
+        assert!(calc.eval_line("let b = a / 0").is_err());
+        assert_eq!(Some(30), calc.variable("b"));
+        assert_eq!("unknown variable 'c'", message("c + 1"));
+    }
+
+    #[test]
+    fn overflow_is_an_error() {
+        assert_eq!(
+            "9223372036854775807 + 1 overflows",
+            message("9223372036854775807 + 1")
+        );
+        assert_eq!(
+            "-9223372036854775808 / -1 overflows",
+            message("(-9223372036854775807 - 1) / -1")
+        );
+        assert_eq!(
+            "-(-9223372036854775808) overflows",
+            message("-(-9223372036854775807 - 1)")
+        );
+        assert_eq!(
+            "9223372036854775808 doesn't fit in 64 bits",
+            message("9223372036854775808")
+        );
+        assert_eq!("division by zero", message("1 % (2 - 2)"));
+    }
+
+    #[test]
+    fn deep_nesting_is_an_error() {
+        let too_deep = "expression nested too deeply";
+        assert_eq!(too_deep, message(&"(".repeat(100_000)));
+        assert_eq!(too_deep, message(&format!("{}1", "- ".repeat(100_000))));
+        assert_eq!(too_deep, message(&format!("1{}", " + 1".repeat(100_000))));
+        assert_eq!(too_deep, message(&format!("1{}", " * (1".repeat(300))));
+
+        let nested = format!("{}1{}", "(".repeat(200), ")".repeat(200));
+        assert_eq!(Ok(Some(1)), eval(&nested));
+        assert_eq!(Ok(Some(200)), eval(&format!("1{}", " + 1".repeat(199))));
+    }
+
+    #[test]
+    fn reports_syntax_errors_with_position() {
+        let err = eval("1 + * 2").unwrap_err();
+        assert_eq!(
+            "expected an expression, found '*'\n  1 + * 2\n      ^",
+            err.to_string()
+        );
+        assert_eq!(4, eval("2 * (3 + 4").unwrap_err().position);
+        assert_eq!("unclosed '('", message("2 * (3 + 4"));
+        assert_eq!("unmatched ')'", message("1 + 2)"));
+        assert_eq!("unexpected '2'", message("1 2 3"));
+        assert_eq!("expected an expression", message("1 +"));
+        assert_eq!("unexpected character '^'", message("2 ^ 3"));
+        assert_eq!("invalid number '12ab'", message("12ab"));
+        assert_eq!("expected '=', found '5'", message("let x 5"));
+        assert_eq!("expected a variable name, found '1'", message("let 1 = 2"));
+    }
+}
@@ -0,0 +1,514 @@
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::fmt;
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::str::FromStr;
+
+// 一个小的 CSV 读取器，按 RFC 4180 处理引号：
+//   - 字段用逗号分隔，记录用 \n 或 \r\n 分隔
+//   - 用双引号括起来的字段里可以有逗号、换行，"" 表示一个双引号
+//   - 不带引号的字段里不能出现双引号
+// 第一条记录是表头，之后的每一行可以按列名取值并转换成需要的类型
+pub struct Reader<'a> {
+    input: &'a str,
+    // 下一个要读的字节位置，以及它所在的行号和列号（都从 1 开始，列按字符计）
+    pos: usize,
+    line: usize,
+    column: usize,
+    // 去掉不带引号的字段前后的空白（RFC 4180 里空白属于字段内容，默认保留）
+    trim: bool,
+    headers: Option<Rc<Headers>>,
+}
+
+// 表头：列名以及列名到下标的映射
+#[derive(Debug)]
+pub struct Headers {
+    names: Vec<String>,
+    index: HashMap<String, usize>,
+}
+
+// 一个字段的内容和它在输入中开始的位置
+#[derive(Debug, Clone, PartialEq)]
+struct Field {
+    value: String,
+    line: usize,
+    column: usize,
+}
+
+// 一条数据记录；通过表头按列名取值
+#[derive(Debug)]
+pub struct Row {
+    fields: Vec<Field>,
+    headers: Rc<Headers>,
+}
+
+// 能从一条记录构造出来的类型，配合 Reader::deserialize 使用
+pub trait FromRecord: Sized {
+    fn from_record(row: &Row) -> Result<Self, Error>;
+}
+
+// 出错的位置和原因；行号和列号都从 1 开始
+#[derive(Debug, PartialEq)]
+pub struct Error {
+    pub line: usize,
+    pub column: usize,
+    pub kind: ErrorKind,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ErrorKind {
+    // 输入是空的，没有表头
+    MissingHeader,
+    // 引号开始之后直到输入结束都没有闭合
+    UnterminatedQuote,
+    // 不带引号的字段里出现了双引号
+    UnexpectedQuote,
+    // 闭合的引号后面跟着的不是逗号或者换行
+    TextAfterQuote,
+    // 字段数和表头的列数不一致
+    FieldCount {
+        expected: usize,
+        found: usize,
+    },
+    // 表头里没有这一列
+    UnknownColumn(String),
+    // 字段的内容不能转换成需要的类型
+    Parse {
+        column: String,
+        value: String,
+        message: String,
+    },
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(input: &'a str) -> Reader<'a> {
+        Reader {
+            input,
+            pos: 0,
+            line: 1,
+            column: 1,
+            trim: false,
+            headers: None,
+        }
+    }
+
+    pub fn trim(mut self, trim: bool) -> Reader<'a> {
+        self.trim = trim;
+        self
+    }
+
+    // 第一次调用时读取表头；表头里有重复的列名时按列名取值得到的是第一个
+    // 表头本身有错时不能把下一行当成表头，剩下的输入全部放弃，之后再调用会得到 MissingHeader
+    pub fn headers(&mut self) -> Result<&Headers, Error> {
+        if self.headers.is_none() {
+            let fields = match self.next_record() {
+                Some(Ok(fields)) => fields,
+                Some(Err(err)) => {
+                    self.pos = self.input.len();
+                    return Err(err);
+                }
+                None => return Err(self.error_here(ErrorKind::MissingHeader)),
+            };
+            let names: Vec<String> = fields.into_iter().map(|f| f.value).collect();
+            let mut index = HashMap::new();
+            for (i, name) in names.iter().enumerate() {
+                index.entry(name.clone()).or_insert(i);
+            }
+            self.headers = Some(Rc::new(Headers { names, index }));
+        }
+        Ok(self.headers.as_ref().unwrap())
+    }
+
+    // 逐条读取数据记录；出错的记录作为 Err 返回，之后继续读下一条
+    pub fn records(&mut self) -> Records<'_, 'a> {
+        Records { reader: self }
+    }
+
+    // 逐条读取并转换成 T
+    pub fn deserialize<T: FromRecord>(
+        &mut self,
+    ) -> impl Iterator<Item = Result<T, Error>> + use<'_, 'a, T> {
+        self.records()
+            .map(|row| row.and_then(|row| T::from_record(&row)))
+    }
+
+    // 读出下一条记录的所有字段；只有一个空字段的记录（空行）会被跳过
+    fn next_record(&mut self) -> Option<Result<Vec<Field>, Error>> {
+        loop {
+            if self.pos >= self.input.len() {
+                return None;
+            }
+            let record = self.read_record();
+            if let Err(err) = &record {
+                self.recover(err);
+            }
+            match &record {
+                Ok(fields) if fields.len() == 1 && fields[0].value.is_empty() => continue,
+                _ => return Some(record),
+            }
+        }
+    }
+
+    fn read_record(&mut self) -> Result<Vec<Field>, Error> {
+        let mut fields = Vec::new();
+        loop {
+            fields.push(self.read_field()?);
+            match self.peek() {
+                Some(',') => {
+                    self.bump();
+                }
+                Some('\r') | Some('\n') => {
+                    self.eat_newline();
+                    return Ok(fields);
+                }
+                None => return Ok(fields),
+                Some(_) => unreachable!("read_field stops at a delimiter"),
+            }
+        }
+    }
+
+    fn read_field(&mut self) -> Result<Field, Error> {
+        if self.trim {
+            self.skip_blanks();
+        }
+        let (line, column) = (self.line, self.column);
+        let mut value = String::new();
+        if self.peek() == Some('"') {
+            self.bump();
+            loop {
+                match self.bump() {
+                    Some('"') if self.peek() == Some('"') => {
+                        self.bump();
+                        value.push('"');
+                    }
+                    Some('"') => break,
+                    Some(c) => value.push(c),
+                    None => {
+                        return Err(Error {
+                            line,
+                            column,
+                            kind: ErrorKind::UnterminatedQuote,
+                        })
+                    }
+                }
+            }
+            if self.trim {
+                self.skip_blanks();
+            }
+            if !self.at_delimiter() {
+                return Err(self.error_here(ErrorKind::TextAfterQuote));
+            }
+        } else {
+            while !self.at_delimiter() {
+                if self.peek() == Some('"') {
+                    return Err(self.error_here(ErrorKind::UnexpectedQuote));
+                }
+                value.extend(self.bump());
+            }
+            if self.trim {
+                value.truncate(value.trim_end().len());
+            }
+        }
+        Ok(Field {
+            value,
+            line,
+            column,
+        })
+    }
+
+    // 出错之后跳到出错位置所在行的末尾，从下一行开始继续读；没闭合的引号会吞掉剩下的全部输入
+    fn recover(&mut self, err: &Error) {
+        if err.kind == ErrorKind::UnterminatedQuote {
+            self.pos = self.input.len();
+            return;
+        }
+        while !matches!(self.peek(), None | Some('\r') | Some('\n')) {
+            self.bump();
+        }
+        self.eat_newline();
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn at_delimiter(&self) -> bool {
+        matches!(self.peek(), None | Some(',') | Some('\n'))
+            || self.input[self.pos..].starts_with("\r\n")
+    }
+
+    fn eat_newline(&mut self) {
+        if self.peek() == Some('\r') {
+            self.bump();
+        }
+        if self.peek() == Some('\n') {
+            self.bump();
+        }
+    }
+
+    fn skip_blanks(&mut self) {
+        while matches!(self.peek(), Some(' ') | Some('\t')) {
+            self.bump();
+        }
+    }
+
+    fn error_here(&self, kind: ErrorKind) -> Error {
+        Error {
+            line: self.line,
+            column: self.column,
+            kind,
+        }
+    }
+}
+
+// Reader::records 返回的迭代器
+pub struct Records<'r, 'a> {
+    reader: &'r mut Reader<'a>,
+}
+
+impl Iterator for Records<'_, '_> {
+    type Item = Result<Row, Error>;
+
+    fn next(&mut self) -> Option<Result<Row, Error>> {
+        let headers = match self.reader.headers() {
+            Ok(_) => Rc::clone(self.reader.headers.as_ref().unwrap()),
+            // 没有表头时只报告一次错误
+            Err(err) if err.kind == ErrorKind::MissingHeader => return None,
+            Err(err) => return Some(Err(err)),
+        };
+        let fields = match self.reader.next_record()? {
+            Ok(fields) => fields,
+            Err(err) => return Some(Err(err)),
+        };
+        if fields.len() != headers.names.len() {
+            let first = &fields[0];
+            return Some(Err(Error {
+                line: first.line,
+                column: first.column,
+                kind: ErrorKind::FieldCount {
+                    expected: headers.names.len(),
+                    found: fields.len(),
+                },
+            }));
+        }
+        Some(Ok(Row { fields, headers }))
+    }
+}
+
+impl Headers {
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    pub fn column(&self, name: &str) -> Option<usize> {
+        self.index.get(name).copied()
+    }
+}
+
+impl Row {
+    // 记录开始的行号
+    pub fn line(&self) -> usize {
+        self.fields[0].line
+    }
+
+    // 按列名取出原始的字符串；表头里没有这一列时是 None
+    pub fn raw(&self, column: &str) -> Option<&str> {
+        let i = self.headers.column(column)?;
+        Some(&self.fields[i].value)
+    }
+
+    // 按列名取值并用 FromStr 转换，失败时错误里带着这个字段的位置
+    pub fn get<T>(&self, column: &str) -> Result<T, Error>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        let field = self.field(column)?;
+        field.value.parse().map_err(|err: T::Err| Error {
+            line: field.line,
+            column: field.column,
+            kind: ErrorKind::Parse {
+                column: column.to_string(),
+                value: field.value.clone(),
+                message: err.to_string(),
+            },
+        })
+    }
+
+    fn field(&self, column: &str) -> Result<&Field, Error> {
+        match self.headers.column(column) {
+            Some(i) => Ok(&self.fields[i]),
+            None => Err(Error {
+                line: self.line(),
+                column: self.fields[0].column,
+                kind: ErrorKind::UnknownColumn(column.to_string()),
+            }),
+        }
+    }
+}
+
+// 按 RFC 4180 写出一条记录：含有逗号、引号或换行的字段放进引号里，引号写成 ""
+pub fn write_record<W: Write>(out: &mut W, fields: &[&str]) -> io::Result<()> {
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            out.write_all(b",")?;
+        }
+        if field.contains([',', '"', '\r', '\n']) {
+            write!(out, "\"{}\"", field.replace('"', "\"\""))?;
+        } else {
+            out.write_all(field.as_bytes())?;
+        }
+    }
+    out.write_all(b"\r\n")
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}: ", self.line, self.column)?;
+        match &self.kind {
+            ErrorKind::MissingHeader => write!(f, "missing header row"),
+            ErrorKind::UnterminatedQuote => write!(f, "quoted field is never closed"),
+            ErrorKind::UnexpectedQuote => write!(f, "unexpected '\"' in unquoted field"),
+            ErrorKind::TextAfterQuote => write!(f, "expected ',' or end of line after '\"'"),
+            ErrorKind::FieldCount { expected, found } => {
+                write!(f, "expected {expected} fields, found {found}")
+            }
+            ErrorKind::UnknownColumn(column) => write!(f, "no column named '{column}'"),
+            ErrorKind::Parse {
+                column,
+                value,
+                message,
+            } => write!(f, "column '{column}': can't parse {value:?}: {message}"),
+        }
+    }
+}
+
+impl StdError for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Penguin {
+        name: String,
+        length: f32,
+    }
+
+    impl FromRecord for Penguin {
+        fn from_record(row: &Row) -> Result<Penguin, Error> {
+            Ok(Penguin {
+                name: row.get("name")?,
+                length: row.get("length")?,
+            })
+        }
+    }
+
+    fn values(row: &Row) -> Vec<&str> {
+        row.fields.iter().map(|f| f.value.as_str()).collect()
+    }
+
+    #[test]
+    fn rfc4180_quoting() {
+        let input = "a,b,c\r\n\"x, y\",\"say \"\"hi\"\"\",\"two\nlines\"\r\n,,\n";
+        let mut reader = Reader::new(input);
+        assert_eq!(&["a", "b", "c"], reader.headers().unwrap().names());
+
+        let rows: Vec<Row> = reader.records().map(Result::unwrap).collect();
+        assert_eq!(vec!["x, y", "say \"hi\"", "two\nlines"], values(&rows[0]));
+        assert_eq!(vec!["", "", ""], values(&rows[1]));
+        assert_eq!(4, rows[1].line());
+    }
+
+    #[test]
+    fn writes_quoted_records() {
+        let mut out = Vec::new();
+        write_record(&mut out, &["a", "b,c", "say \"hi\""]).unwrap();
+        assert_eq!(
+            "a,\"b,c\",\"say \"\"hi\"\"\"\r\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn typed_rows_and_errors() {
+        let input = "name,length\nLittle penguin,33\nInvalid,data\n\nYellow-eyed \"penguin,65\nshort\nFiordland penguin , 60\n";
+        let mut reader = Reader::new(input).trim(true);
+        let results: Vec<Result<Penguin, Error>> = reader.deserialize().collect();
+
+        assert_eq!(
+            Ok(Penguin {
+                name: "Little penguin".to_string(),
+                length: 33.0
+            }),
+            results[0]
+        );
+        let err = results[1].as_ref().unwrap_err();
+        assert_eq!((3, 9), (err.line, err.column));
+        assert_eq!(
+            "line 3, column 9: column 'length': can't parse \"data\": invalid float literal",
+            err.to_string()
+        );
+        let err = results[2].as_ref().unwrap_err();
+        assert_eq!(
+            (5, 13, &ErrorKind::UnexpectedQuote),
+            (err.line, err.column, &err.kind)
+        );
+        let err = results[3].as_ref().unwrap_err();
+        assert_eq!(
+            &ErrorKind::FieldCount {
+                expected: 2,
+                found: 1
+            },
+            &err.kind
+        );
+        assert_eq!(60.0, results[4].as_ref().unwrap().length);
+        assert_eq!(5, results.len());
+    }
+
+    #[test]
+    fn header_error_is_fatal() {
+        let mut reader = Reader::new("name,len\"gth\nLittle penguin,33\nFairy penguin,30\n");
+        let results: Vec<Result<Row, Error>> = reader.records().collect();
+
+        assert_eq!(1, results.len());
+        let err = results[0].as_ref().unwrap_err();
+        assert_eq!(
+            (1, 9, &ErrorKind::UnexpectedQuote),
+            (err.line, err.column, &err.kind)
+        );
+        assert_eq!(ErrorKind::MissingHeader, reader.headers().unwrap_err().kind);
+    }
+
+    #[test]
+    fn unterminated_quote_and_unknown_column() {
+        let mut reader = Reader::new("a\n\"open\nmore\n");
+        let err = reader.records().next().unwrap().unwrap_err();
+        assert_eq!(
+            (2, 1, ErrorKind::UnterminatedQuote),
+            (err.line, err.column, err.kind)
+        );
+        assert!(reader.records().next().is_none());
+
+        let mut reader = Reader::new("a\n1\n");
+        let row = reader.records().next().unwrap().unwrap();
+        assert_eq!(Some("1"), row.raw("a"));
+        assert_eq!(
+            ErrorKind::UnknownColumn("b".to_string()),
+            row.get::<i32>("b").unwrap_err().kind
+        );
+    }
+}
@@ -0,0 +1,409 @@
+use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+use std::fmt;
+
+// 每个语言一个消息文件，编译时嵌入程序。文件格式：
+//   # 注释
+//   greeting = Hello, {name}!
+//   greeted[one] = Greeted {count} person.
+//   greeted[other] = Greeted {count} people.
+// {name} 是占位符，{{ 和 }} 表示字面的花括号；[one]/[other] 等是复数形式，按语言的复数规则选择
+const CATALOGS: &[(&str, &str)] = &[
+    ("en", include_str!("../locales/en.txt")),
+    ("de", include_str!("../locales/de.txt")),
+    ("de-AT", include_str!("../locales/de-AT.txt")),
+    ("zh", include_str!("../locales/zh.txt")),
+];
+
+// 所有语言最后都回退到英语
+const DEFAULT_LOCALE: &str = "en";
+
+const PLURAL_CATEGORIES: &[&str] = &["zero", "one", "two", "few", "many", "other"];
+
+// 一个语言的所有消息；复数形式的键是 "key[one]" 这样的形式
+pub struct Catalog {
+    locale: String,
+    messages: HashMap<String, String>,
+}
+
+// 按回退顺序排列的消息目录，例如 de-AT → de → en
+pub struct Messages {
+    catalogs: Vec<Catalog>,
+}
+
+// 消息文件的格式错误
+#[derive(Debug, PartialEq)]
+pub struct CatalogError {
+    pub locale: String,
+    pub line: usize,
+    pub message: String,
+}
+
+// 取消息时的错误；调用方可以报告出来然后继续，不会 panic
+#[derive(Debug, PartialEq)]
+pub enum MessageError {
+    // 回退链上所有的语言都没有这个键
+    MissingKey { key: String, locale: String },
+    // 消息里的占位符没有给出对应的值
+    MissingArgument { key: String, argument: String },
+}
+
+impl Catalog {
+    pub fn parse(locale: &str, text: &str) -> Result<Catalog, CatalogError> {
+        let error = |line: usize, message: String| CatalogError {
+            locale: locale.to_string(),
+            line,
+            message,
+        };
+        let mut messages = HashMap::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(error(i + 1, "expected 'key = message'".to_string()));
+            };
+            let (key, value) = (key.trim(), value.trim());
+            if !valid_key(key) {
+                return Err(error(i + 1, format!("invalid key '{key}'")));
+            }
+            check_braces(value).map_err(|message| error(i + 1, message))?;
+            if messages
+                .insert(key.to_string(), value.to_string())
+                .is_some()
+            {
+                return Err(error(i + 1, format!("duplicate key '{key}'")));
+            }
+        }
+        Ok(Catalog {
+            locale: locale.to_string(),
+            messages,
+        })
+    }
+
+    fn lookup(&self, key: &str) -> Option<&str> {
+        self.messages.get(key).map(String::as_str)
+    }
+}
+
+impl Messages {
+    // 加载 locale 的回退链上所有内置的消息目录；locale 已经按 normalize 规范化
+    pub fn load(locale: &str) -> Result<Messages, CatalogError> {
+        let mut catalogs = Vec::new();
+        for tag in fallback_chain(locale) {
+            if let Some((tag, text)) = CATALOGS.iter().find(|(t, _)| *t == tag) {
+                catalogs.push(Catalog::parse(tag, text)?);
+            }
+        }
+        Ok(Messages { catalogs })
+    }
+
+    // 实际用到的第一个语言
+    pub fn locale(&self) -> &str {
+        self.catalogs
+            .first()
+            .map_or(DEFAULT_LOCALE, |c| c.locale.as_str())
+    }
+
+    pub fn get(&self, key: &str, args: &[(&str, &str)]) -> Result<String, MessageError> {
+        self.format(key, None, args)
+    }
+
+    // 按 count 选择复数形式；消息里可以用 {count}
+    pub fn plural(
+        &self,
+        key: &str,
+        count: u64,
+        args: &[(&str, &str)],
+    ) -> Result<String, MessageError> {
+        self.format(key, Some(count), args)
+    }
+
+    fn format(
+        &self,
+        key: &str,
+        count: Option<u64>,
+        args: &[(&str, &str)],
+    ) -> Result<String, MessageError> {
+        let find = |key: &dyn Fn(&Catalog) -> String| {
+            self.catalogs
+                .iter()
+                .find_map(|catalog| catalog.lookup(&key(catalog)))
+        };
+        let template = match count {
+            None => find(&|_| key.to_string()),
+            // 先在整条回退链上找对应的复数类别，都找不到时再用 other，
+            // 否则 de-AT 里的 key[other] 会盖过 de 里更合适的 key[one]
+            Some(n) => find(&|catalog| format!("{key}[{}]", plural_category(&catalog.locale, n)))
+                .or_else(|| find(&|_| format!("{key}[other]"))),
+        }
+        .ok_or_else(|| MessageError::MissingKey {
+            key: key.to_string(),
+            locale: self.locale().to_string(),
+        })?;
+        let count = count.map(|n| n.to_string());
+        let mut out = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(i) = rest.find(['{', '}']) {
+            out.push_str(&rest[..i]);
+            // check_braces 已经保证了花括号是成对的
+            if rest[i..].starts_with("{{") || rest[i..].starts_with("}}") {
+                out.push_str(&rest[i..i + 1]);
+                rest = &rest[i + 2..];
+                continue;
+            }
+            let end = i + rest[i..].find('}').unwrap();
+            let name = &rest[i + 1..end];
+            let value = match (name, &count) {
+                ("count", Some(count)) => count.as_str(),
+                _ => args
+                    .iter()
+                    .find(|(n, _)| *n == name)
+                    .map(|(_, v)| *v)
+                    .ok_or_else(|| MessageError::MissingArgument {
+                        key: key.to_string(),
+                        argument: name.to_string(),
+                    })?,
+            };
+            out.push_str(value);
+            rest = &rest[end + 1..];
+        }
+        out.push_str(rest);
+        Ok(out)
+    }
+}
+
+// 和 POSIX 一样，LC_ALL 优先于 LC_MESSAGES，再是 LANG；都没有设置或者是 C/POSIX 时返回 None
+pub fn locale_from_env() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .and_then(|value| normalize(&value))
+}
+
+// 把 de_AT.UTF-8、de-at、zh_CN@pinyin 这样的写法统一成 de-AT、zh-CN
+pub fn normalize(locale: &str) -> Option<String> {
+    let tag = locale.split(['.', '@']).next().unwrap_or("");
+    if tag.is_empty() || tag == "C" || tag == "POSIX" {
+        return None;
+    }
+    let mut parts = tag.split(['_', '-']);
+    let language = parts.next()?.to_ascii_lowercase();
+    if language.is_empty() || !language.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    Some(match parts.next() {
+        Some(region) if !region.is_empty() => format!("{language}-{}", region.to_ascii_uppercase()),
+        _ => language,
+    })
+}
+
+// de-AT → [de-AT, de, en]
+fn fallback_chain(locale: &str) -> Vec<String> {
+    let mut chain = vec![locale.to_string()];
+    if let Some((language, _)) = locale.split_once('-') {
+        chain.push(language.to_string());
+    }
+    if !chain.iter().any(|tag| tag == DEFAULT_LOCALE) {
+        chain.push(DEFAULT_LOCALE.to_string());
+    }
+    chain
+}
+
+// CLDR 复数规则的一个子集，只处理整数
+fn plural_category(locale: &str, n: u64) -> &'static str {
+    let language = locale.split('-').next().unwrap_or(locale);
+    match language {
+        // 中文、日文、韩文没有单复数之分
+        "zh" | "ja" | "ko" => "other",
+        // 法语里 0 也用单数
+        "fr" => {
+            if n <= 1 {
+                "one"
+            } else {
+                "other"
+            }
+        }
+        _ => {
+            if n == 1 {
+                "one"
+            } else {
+                "other"
+            }
+        }
+    }
+}
+
+fn valid_key(key: &str) -> bool {
+    let (name, category) = match key.strip_suffix(']').and_then(|k| k.split_once('[')) {
+        Some((name, category)) => (name, Some(category)),
+        None => (key, None),
+    };
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        && category.is_none_or(|c| PLURAL_CATEGORIES.contains(&c))
+}
+
+fn check_braces(value: &str) -> Result<(), String> {
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+            }
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => {
+                            return Err("unclosed '{' (write '{{' for a literal brace)".to_string())
+                        }
+                    }
+                }
+                if name.is_empty() || name.contains('{') {
+                    return Err("placeholder needs a name, e.g. {name}".to_string());
+                }
+            }
+            '}' => return Err("unmatched '}' (write '}}' for a literal brace)".to_string()),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+impl fmt::Display for CatalogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "locales/{}.txt:{}: {}",
+            self.locale, self.line, self.message
+        )
+    }
+}
+
+impl Error for CatalogError {}
+
+impl fmt::Display for MessageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MessageError::MissingKey { key, locale } => {
+                write!(f, "no message '{key}' for locale {locale} or its fallbacks")
+            }
+            MessageError::MissingArgument { key, argument } => {
+                write!(f, "message '{key}' needs a value for {{{argument}}}")
+            }
+        }
+    }
+}
+
+impl Error for MessageError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_catalogs_parse() {
+        for (locale, text) in CATALOGS {
+            if let Err(err) = Catalog::parse(locale, text) {
+                panic!("{err}");
+            }
+        }
+    }
+
+    #[test]
+    fn falls_back_through_the_chain() {
+        let messages = Messages::load("de-AT").unwrap();
+        assert_eq!("de-AT", messages.locale());
+        assert_eq!(
+            Ok("Grüß Gott, Welt!".to_string()),
+            messages.get("greeting", &[("name", "Welt")])
+        );
+        assert_eq!(Ok("Welt".to_string()), messages.get("world", &[]));
+        assert_eq!(
+            Ok("2 Personen begrüßt.".to_string()),
+            messages.plural("greeted", 2, &[])
+        );
+
+        // 没有内置目录的语言直接用英语
+        let messages = Messages::load("pt-BR").unwrap();
+        assert_eq!("en", messages.locale());
+        assert_eq!(
+            Ok("Greeted 1 person.".to_string()),
+            messages.plural("greeted", 1, &[])
+        );
+        assert_eq!(
+            Err(MessageError::MissingKey {
+                key: "farewell".to_string(),
+                locale: "en".to_string()
+            }),
+            messages.get("farewell", &[])
+        );
+    }
+
+    #[test]
+    fn plural_rules_and_placeholders() {
+        let messages = Messages::load("zh").unwrap();
+        assert_eq!(
+            Ok("问候了 1 个人。".to_string()),
+            messages.plural("greeted", 1, &[])
+        );
+
+        let catalog = Catalog::parse(
+            "fr",
+            "n[one] = {count} chat\nn[other] = {count} chats\nb = {{x}} {y}",
+        )
+        .unwrap();
+        let messages = Messages {
+            catalogs: vec![catalog],
+        };
+        assert_eq!(Ok("0 chat".to_string()), messages.plural("n", 0, &[]));
+        assert_eq!(Ok("{x} 1".to_string()), messages.get("b", &[("y", "1")]));
+        assert!(matches!(
+            messages.get("b", &[]),
+            Err(MessageError::MissingArgument { .. })
+        ));
+
+        // 回退链上的 one 比当前目录里的 other 优先
+        let messages = Messages {
+            catalogs: vec![
+                Catalog::parse("de-AT", "greeted[other] = {count} Leute gegrüßt.").unwrap(),
+                Catalog::parse("de", "greeted[one] = {count} Person begrüßt.").unwrap(),
+            ],
+        };
+        assert_eq!(
+            Ok("1 Person begrüßt.".to_string()),
+            messages.plural("greeted", 1, &[])
+        );
+        assert_eq!(
+            Ok("3 Leute gegrüßt.".to_string()),
+            messages.plural("greeted", 3, &[])
+        );
+    }
+
+    #[test]
+    fn catalog_and_locale_errors() {
+        let err = Catalog::parse("en", "a = 1\n\nb[some] = 2").err().unwrap();
+        assert_eq!(
+            (3, "invalid key 'b[some]'"),
+            (err.line, err.message.as_str())
+        );
+        assert!(Catalog::parse("en", "a = {name").is_err());
+        assert!(Catalog::parse("en", "a = 1\na = 2").is_err());
+
+        assert_eq!(Some("de-AT".to_string()), normalize("de_AT.UTF-8"));
+        assert_eq!(Some("zh-CN".to_string()), normalize("zh_cn@pinyin"));
+        assert_eq!(None, normalize("C.UTF-8"));
+        assert_eq!(vec!["de-AT", "de", "en"], fallback_chain("de-AT"));
+    }
+}
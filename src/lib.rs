@@ -0,0 +1,5 @@
+// main.rs 里的命令行工具用到的模块
+pub mod calc;
+pub mod csv;
+pub mod i18n;
+pub mod report;
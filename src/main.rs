@@ -1,59 +1,367 @@
-fn greet_world() {
-    let southern_germany = "Grüß Gott!";
-    let chinese = "世界你好！";
-    let english = "World, hello!";
-    let regions = [southern_germany, chinese, english];
-    // 不能直接进行循环，需要变成迭代器(.iter() 方法)
-    for region in regions.iter() {
-        // 宏操作符（特殊类型函数）: !
-        // 占位符：{}
-        println!("{}", &region)
-    }
-}
-
-fn xiamawei() {
-    let penguin_data = "\
-    common name,length (cm)
-    Little penguin,33
-    Yellow-eyed penguin,65
-    Fiordland penguin,60
-    Invalid,data
-    ,
-    ";
-
-    let records = penguin_data.lines();
-    // TODO: 不懂为什么用 enumerate
-    for (i, record) in records.enumerate() {
-        if i == 0 || record.trim().len() == 0 {
-            continue;
-        }
-        // 声明一个 fields 变量，类型是 Vec
-        // Vec 是 vector 的缩写，是一个可伸缩的集合类型，可以认为是一个动态数组
-        // <_> 表示 Vec 中的元素类型由编译器自行推断，在很多场景下，都会帮我们省却不少功夫
-        let fields: Vec<_> = record.split(',').map(|field| field.trim()).collect();
-        // if cfg!(debug_assertions)，说明紧跟其后的输出（打印）只在 debug 模式下生效。
-        if cfg!(debug_assertions) {
-            // 输出到标准错误输出
-            // {:?} 啥意思，是跟 eprintln 绑定的吗，应该不是
-            // 是执行输出引用（不一定）对象
-            eprintln!("debug: {:?} -> {:?}", record, fields);
-        }
-        let name = fields[0];
-        // 1. 尝试把 fields[1] 的值转换为 f32 类型的浮点数，如果成功，则把 f32 值赋给 length 变量
-        // 2. If let 是一个匹配表达式，用来从 = 右边的结果中，匹配出 length 的值:
-        //  1) 当 = 右边的表达式执行成功，则会返回一个 Ok(f32) 的类型，若失败，则会返回一个 Err(e) 类型
-        //     if let 的作用就是仅匹配 Ok 也就是成功的情况，如果是错误，就直接忽略
-        //  2) 同时 if let 还会做一次解构匹配，通过 Ok(length) 去匹配右边的 Ok(f32)，最终把相应的 f32 值赋给 length
-        //  3) 当然你也可以忽略成功的情况，用 if let Err(e) = fields[1].parse::<f32>() {...}匹配出错误，然后打印出来，但是没用
-        // Ok 是关键字吗?
-        // 是枚举 Result::Ok
-        if let Ok(length) = fields[1].parse::<f32>() {
-            println!("{}, {}cm", name, length)
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+use std::process;
+
+use hello_world::calc::Calculator;
+use hello_world::i18n::{self, MessageError, Messages};
+use hello_world::report::{Dataset, Format};
+
+mod study;
+
+// 用当前语言的消息目录问候每个名字，没有名字时问候“世界”
+// 缺少的消息报告到 stderr，并用消息的键代替，不会 panic
+fn greet_world(messages: &Messages, names: &[String]) {
+    let text = |result: Result<String, MessageError>, key: &str| {
+        result.unwrap_or_else(|err| {
+            eprintln!("hello_world: {err}");
+            key.to_string()
+        })
+    };
+    let names = if names.is_empty() {
+        vec![text(messages.get("world", &[]), "world")]
+    } else {
+        names.to_vec()
+    };
+    for name in &names {
+        // 占位符：{name}
+        println!(
+            "{}",
+            text(messages.get("greeting", &[("name", name)]), "greeting")
+        );
+    }
+    let count = names.len() as u64;
+    println!(
+        "{}",
+        text(messages.plural("greeted", count, &[]), "greeted")
+    );
+}
+
+const USAGE: &str = "\
+Usage: hello_world [OPTIONS] [FILE]...
+       hello_world greet [--locale LOCALE] [NAME]...
+       hello_world calc [-e EXPR]...
+       hello_world xiamawei [--csv]
+
+Summarize every numeric column of CSV files that start with a header row:
+count, min, max, mean, median and sample standard deviation.
+With no FILE, or when FILE is -, read standard input.
+
+Options:
+      --group-by COLUMN   Summarize separately for each value of COLUMN
+      --format FORMAT     Print an aligned 'table' (default) or 'csv'
+  -h, --help              Print this help
+
+The greet command greets every NAME (or the world) in the language given by
+--locale, or else by LC_ALL, LC_MESSAGES or LANG; a locale such as de-AT falls
+back to de and then to en for messages it does not translate.
+
+The calc command evaluates 64-bit integer expressions with + - * / %,
+parentheses and variables ('let x = 1 + 2'); overflow is an error. Each -e
+EXPR is evaluated in order, otherwise lines are read from standard input.
+
+The xiamawei command runs the original penguin study code; with --csv it reads
+the same data through the csv module.
+";
+
+// 要执行的子命令
+#[derive(Debug, PartialEq)]
+enum Command {
+    Report(Args),
+    Greet {
+        locale: Option<String>,
+        names: Vec<String>,
+    },
+    Calc {
+        exprs: Vec<String>,
+    },
+    Xiamawei {
+        csv: bool,
+    },
+    Help,
+}
+
+// 命令行参数
+#[derive(Debug, Default, PartialEq)]
+struct Args {
+    files: Vec<String>,
+    group_by: Option<String>,
+    format: Format,
+}
+
+fn parse_args(args: &[String]) -> Result<Command, String> {
+    match args.get(1).map(String::as_str) {
+        Some("greet") => return parse_greet(&args[2..]),
+        Some("calc") => return parse_calc(&args[2..]),
+        Some("xiamawei") => return parse_xiamawei(&args[2..]),
+        _ => {}
+    }
+    let mut parsed = Args::default();
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        // 同时支持 --name VALUE 和 --name=VALUE
+        let (name, inline) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => (name, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+        let mut value = || {
+            inline
+                .clone()
+                .or_else(|| iter.next().cloned())
+                .ok_or(format!("{name} needs a value"))
+        };
+        match name {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--group-by" => parsed.group_by = Some(value()?),
+            "--format" => {
+                parsed.format = match value()?.as_str() {
+                    "table" => Format::Table,
+                    "csv" => Format::Csv,
+                    other => {
+                        return Err(format!(
+                            "invalid format '{other}' (expected 'table' or 'csv')"
+                        ))
+                    }
+                }
+            }
+            _ if name.starts_with('-') && name != "-" => {
+                return Err(format!("unknown option '{name}' (see --help)"))
+            }
+            _ => parsed.files.push(arg.clone()),
         }
     }
+    if parsed.files.is_empty() {
+        parsed.files.push("-".to_string());
+    }
+    Ok(Command::Report(parsed))
+}
+
+// greet 的参数：--locale 之外的都是名字
+fn parse_greet(args: &[String]) -> Result<Command, String> {
+    let mut locale = None;
+    let mut names = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let tag = match arg.strip_prefix("--locale") {
+            Some("") => Some(iter.next().ok_or("--locale needs a value")?.clone()),
+            Some(value) if value.starts_with('=') => Some(value[1..].to_string()),
+            _ => None,
+        };
+        match tag {
+            Some(tag) => {
+                locale = Some(i18n::normalize(&tag).ok_or(format!("invalid locale '{tag}'"))?)
+            }
+            None if arg == "-h" || arg == "--help" => return Ok(Command::Help),
+            None if arg.starts_with('-') => {
+                return Err(format!("unknown option '{arg}' (see --help)"))
+            }
+            None => names.push(arg.clone()),
+        }
+    }
+    Ok(Command::Greet { locale, names })
+}
+
+// calc 的参数：每个 -e 带一个表达式；表达式本身可以以 - 开头，所以 -e 后面的值原样接收
+fn parse_calc(args: &[String]) -> Result<Command, String> {
+    let mut exprs = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-e" | "--expr" => {
+                exprs.push(iter.next().ok_or(format!("{arg} needs a value"))?.clone())
+            }
+            _ => match arg.strip_prefix("--expr=") {
+                Some(expr) => exprs.push(expr.to_string()),
+                None => return Err(format!("unexpected argument '{arg}' (see --help)")),
+            },
+        }
+    }
+    Ok(Command::Calc { exprs })
+}
+
+fn parse_xiamawei(args: &[String]) -> Result<Command, String> {
+    let mut csv = false;
+    for arg in args {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--csv" => csv = true,
+            _ => return Err(format!("unexpected argument '{arg}' (see --help)")),
+        }
+    }
+    Ok(Command::Xiamawei { csv })
+}
+
+fn run(args: &Args) -> Result<(), String> {
+    let mut data = Dataset::new();
+    let mut warnings = Vec::new();
+    for file in &args.files {
+        let (source, text) = if file == "-" {
+            let mut text = String::new();
+            io::stdin()
+                .read_to_string(&mut text)
+                .map_err(|err| format!("<stdin>: {err}"))?;
+            ("<stdin>", text)
+        } else {
+            let text = fs::read_to_string(file).map_err(|err| format!("{file}: {err}"))?;
+            (file.as_str(), text)
+        };
+        warnings.extend(data.add(source, &text));
+    }
+    let report = data.summarize(args.group_by.as_deref())?;
+    // 坏的行和不是数字的值都报告出来，但不影响其余数据的汇总
+    for warning in warnings.iter().chain(&report.warnings) {
+        eprintln!("warning: {warning}");
+    }
+    report
+        .write(args.format, &mut io::stdout().lock())
+        .map_err(|err| err.to_string())
+}
+
+fn greet(locale: Option<String>, names: &[String]) -> Result<(), String> {
+    let locale = locale
+        .or_else(i18n::locale_from_env)
+        .unwrap_or_else(|| "en".to_string());
+    let messages = Messages::load(&locale).map_err(|err| err.to_string())?;
+    greet_world(&messages, names);
+    Ok(())
+}
+
+// 有 -e 时依次计算每个表达式，遇到错误就停止；否则是交互式的 REPL，出错后继续读下一行
+fn calc(exprs: &[String]) -> Result<(), String> {
+    let mut calculator = Calculator::new();
+    for expr in exprs {
+        if let Some(value) = calculator.eval_line(expr).map_err(|err| err.to_string())? {
+            println!("{value}");
+        }
+    }
+    if !exprs.is_empty() {
+        return Ok(());
+    }
+
+    let stdin = io::stdin();
+    // 输入来自管道时不显示提示符
+    let prompt = stdin.is_terminal();
+    let mut lines = stdin.lock().lines();
+    loop {
+        if prompt {
+            print!("> ");
+            io::stdout().flush().map_err(|err| err.to_string())?;
+        }
+        let Some(line) = lines.next() else {
+            break;
+        };
+        let line = line.map_err(|err| format!("<stdin>: {err}"))?;
+        match calculator.eval_line(&line) {
+            Ok(Some(value)) => println!("{value}"),
+            Ok(None) => {}
+            Err(err) => eprintln!("error: {err}"),
+        }
+    }
+    if prompt {
+        println!();
+    }
+    Ok(())
 }
 
 fn main() {
-    // greet_world();
-    xiamawei();
+    let args: Vec<String> = env::args().collect();
+    let result = match parse_args(&args) {
+        Ok(Command::Report(args)) => run(&args),
+        Ok(Command::Greet { locale, names }) => greet(locale, &names),
+        Ok(Command::Calc { exprs }) => calc(&exprs),
+        Ok(Command::Xiamawei { csv }) => {
+            if csv {
+                study::xiamawei_csv();
+            } else {
+                study::main();
+            }
+            return;
+        }
+        Ok(Command::Help) => {
+            print!("{USAGE}");
+            return;
+        }
+        Err(err) => Err(err),
+    };
+    if let Err(err) = result {
+        eprintln!("hello_world: {err}");
+        process::exit(2);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Command, String> {
+        let args: Vec<String> = std::iter::once("hello_world")
+            .chain(args.iter().copied())
+            .map(String::from)
+            .collect();
+        parse_args(&args)
+    }
+
+    fn report(args: &[&str]) -> Args {
+        match parse(args) {
+            Ok(Command::Report(args)) => args,
+            other => panic!("{other:?}"),
+        }
+    }
+
+    #[test]
+    fn parses_options_and_files() {
+        let args = report(&["--group-by=common name", "--format", "csv", "a.csv", "-"]);
+        assert_eq!(Some("common name".to_string()), args.group_by);
+        assert_eq!(Format::Csv, args.format);
+        assert_eq!(vec!["a.csv", "-"], args.files);
+
+        assert_eq!(vec!["-"], report(&[]).files);
+        assert_eq!(Ok(Command::Help), parse(&["-h"]));
+        assert!(parse(&["--format", "xml"]).is_err());
+        assert!(parse(&["--group-by"]).is_err());
+    }
+
+    #[test]
+    fn parses_greet() {
+        assert_eq!(
+            Ok(Command::Greet {
+                locale: Some("de-AT".to_string()),
+                names: vec!["Ferris".to_string(), "Anna".to_string()],
+            }),
+            parse(&["greet", "Ferris", "--locale", "de_AT.UTF-8", "Anna"])
+        );
+        assert_eq!(
+            Ok(Command::Greet {
+                locale: Some("zh".to_string()),
+                names: Vec::new(),
+            }),
+            parse(&["greet", "--locale=zh"])
+        );
+        assert!(parse(&["greet", "--locale", "C"]).is_err());
+        assert!(parse(&["greet", "--locale"]).is_err());
+    }
+
+    #[test]
+    fn parses_calc() {
+        assert_eq!(
+            Ok(Command::Calc {
+                exprs: vec!["let x = 2".to_string(), "-x * 3".to_string()],
+            }),
+            parse(&["calc", "-e", "let x = 2", "--expr=-x * 3"])
+        );
+        assert_eq!(Ok(Command::Calc { exprs: Vec::new() }), parse(&["calc"]));
+        assert!(parse(&["calc", "-e"]).is_err());
+        assert!(parse(&["calc", "1 + 2"]).is_err());
+    }
+
+    #[test]
+    fn parses_xiamawei() {
+        assert_eq!(Ok(Command::Xiamawei { csv: false }), parse(&["xiamawei"]));
+        assert_eq!(
+            Ok(Command::Xiamawei { csv: true }),
+            parse(&["xiamawei", "--csv"])
+        );
+        assert!(parse(&["xiamawei", "data.csv"]).is_err());
+    }
 }
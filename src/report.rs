@@ -0,0 +1,347 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::csv;
+
+// 一列数值的汇总；stddev 是样本标准差（除以 n - 1），只有一个值时没有定义
+#[derive(Debug, Clone, PartialEq)]
+pub struct Summary {
+    pub count: usize,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub median: f64,
+    pub stddev: Option<f64>,
+}
+
+impl Summary {
+    // values 为空时返回 None
+    pub fn of(values: &[f64]) -> Option<Summary> {
+        if values.is_empty() {
+            return None;
+        }
+        let mut sorted = values.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let count = sorted.len();
+        let mean = sorted.iter().sum::<f64>() / count as f64;
+        let median = if count % 2 == 1 {
+            sorted[count / 2]
+        } else {
+            (sorted[count / 2 - 1] + sorted[count / 2]) / 2.0
+        };
+        let stddev = (count > 1).then(|| {
+            let squares: f64 = sorted.iter().map(|x| (x - mean) * (x - mean)).sum();
+            (squares / (count - 1) as f64).sqrt()
+        });
+        Some(Summary {
+            count,
+            min: sorted[0],
+            max: sorted[count - 1],
+            mean,
+            median,
+            stddev,
+        })
+    }
+}
+
+// 输出格式：对齐的文本表格或者 CSV
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Format {
+    #[default]
+    Table,
+    Csv,
+}
+
+// 读入数据时被跳过的行，或者数值列里不是数字的值
+#[derive(Debug)]
+pub struct Warning {
+    pub source: String,
+    pub error: csv::Error,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.source, self.error)
+    }
+}
+
+// 从一个或多个 CSV 输入收集到的行；不同输入的列按列名对应起来
+#[derive(Default)]
+pub struct Dataset {
+    // 所有输入的列名，按第一次出现的顺序
+    columns: Vec<String>,
+    rows: Vec<(String, csv::Row)>,
+}
+
+// 汇总结果：每个（分组，数值列）一行
+#[derive(Debug)]
+pub struct Report {
+    pub group_by: Option<String>,
+    pub lines: Vec<ReportLine>,
+    pub warnings: Vec<Warning>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ReportLine {
+    pub group: Option<String>,
+    pub column: String,
+    pub summary: Summary,
+}
+
+impl Dataset {
+    pub fn new() -> Dataset {
+        Dataset::default()
+    }
+
+    // 读入一个输入，source 是它的名字（文件名或者 <stdin>）
+    // 坏的行不会被悄悄丢掉：它们作为警告返回，其余的行照常收集
+    pub fn add(&mut self, source: &str, text: &str) -> Vec<Warning> {
+        let mut reader = csv::Reader::new(text);
+        let warning = |error| Warning {
+            source: source.to_string(),
+            error,
+        };
+        match reader.headers() {
+            Ok(headers) => {
+                for name in headers.names() {
+                    if !self.columns.contains(name) {
+                        self.columns.push(name.clone());
+                    }
+                }
+            }
+            // 空输入没有可以汇总的内容
+            Err(err) if err.kind == csv::ErrorKind::MissingHeader => return Vec::new(),
+            Err(err) => return vec![warning(err)],
+        }
+        let mut warnings = Vec::new();
+        for row in reader.records() {
+            match row {
+                Ok(row) => self.rows.push((source.to_string(), row)),
+                Err(err) => warnings.push(warning(err)),
+            }
+        }
+        warnings
+    }
+
+    // 汇总每一个数值列；group_by 给出时按这一列的值分组，分组按字典序排列
+    // 一列里超过一半的非空值是数字时才算数值列，其余不是数字的值作为警告报告
+    pub fn summarize(&self, group_by: Option<&str>) -> Result<Report, String> {
+        if let Some(name) = group_by {
+            if !self.columns.iter().any(|c| c == name) {
+                return Err(format!("no column named '{name}'"));
+            }
+        }
+
+        let mut warnings = Vec::new();
+        let numeric: Vec<&String> = self
+            .columns
+            .iter()
+            .filter(|column| Some(column.as_str()) != group_by && self.is_numeric(column))
+            .collect();
+        // 分组 -> 每个数值列的值
+        let mut groups: BTreeMap<Option<&str>, Vec<Vec<f64>>> = BTreeMap::new();
+        for (source, row) in &self.rows {
+            let group = group_by.map(|name| row.raw(name).unwrap_or(""));
+            let values = groups
+                .entry(group)
+                .or_insert_with(|| vec![Vec::new(); numeric.len()]);
+            for (i, column) in numeric.iter().enumerate() {
+                // 这个输入里没有这一列，或者值是空的，都当作缺失
+                if row.raw(column).is_none_or(str::is_empty) {
+                    continue;
+                }
+                match row.get::<f64>(column) {
+                    Ok(value) => values[i].push(value),
+                    Err(error) => warnings.push(Warning {
+                        source: source.clone(),
+                        error,
+                    }),
+                }
+            }
+        }
+
+        let mut lines = Vec::new();
+        for (group, values) in groups {
+            for (column, values) in numeric.iter().zip(values) {
+                if let Some(summary) = Summary::of(&values) {
+                    lines.push(ReportLine {
+                        group: group.map(str::to_string),
+                        column: column.to_string(),
+                        summary,
+                    });
+                }
+            }
+        }
+        Ok(Report {
+            group_by: group_by.map(str::to_string),
+            lines,
+            warnings,
+        })
+    }
+
+    fn is_numeric(&self, column: &str) -> bool {
+        let (mut present, mut numbers) = (0, 0);
+        for (_, row) in &self.rows {
+            let Some(value) = row.raw(column).filter(|v| !v.is_empty()) else {
+                continue;
+            };
+            present += 1;
+            if value.parse::<f64>().is_ok() {
+                numbers += 1;
+            }
+        }
+        numbers > 0 && numbers * 2 > present
+    }
+}
+
+impl Report {
+    pub fn write(&self, format: Format, out: &mut impl Write) -> io::Result<()> {
+        let mut header = Vec::new();
+        if let Some(name) = &self.group_by {
+            header.push(name.as_str());
+        }
+        header.extend(["column", "count", "min", "max", "mean", "median", "stddev"]);
+        let rows: Vec<Vec<String>> = self.lines.iter().map(|line| self.cells(line)).collect();
+
+        match format {
+            Format::Csv => {
+                csv::write_record(out, &header)?;
+                for row in &rows {
+                    let row: Vec<&str> = row.iter().map(String::as_str).collect();
+                    csv::write_record(out, &row)?;
+                }
+                Ok(())
+            }
+            Format::Table => {
+                // 文本列（分组和列名）左对齐，数字右对齐；宽度按字符数计算
+                let text_columns = header.len() - 6;
+                let mut widths: Vec<usize> = header.iter().map(|h| h.chars().count()).collect();
+                for row in &rows {
+                    for (width, cell) in widths.iter_mut().zip(row) {
+                        *width = (*width).max(cell.chars().count());
+                    }
+                }
+                let header: Vec<String> = header.iter().map(|h| h.to_string()).collect();
+                for row in std::iter::once(&header).chain(&rows) {
+                    let mut line = String::new();
+                    for (i, (cell, width)) in row.iter().zip(&widths).enumerate() {
+                        if i > 0 {
+                            line.push_str("  ");
+                        }
+                        let pad = " ".repeat(width - cell.chars().count());
+                        if i < text_columns {
+                            line.push_str(cell);
+                            line.push_str(&pad);
+                        } else {
+                            line.push_str(&pad);
+                            line.push_str(cell);
+                        }
+                    }
+                    writeln!(out, "{}", line.trim_end())?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn cells(&self, line: &ReportLine) -> Vec<String> {
+        let s = &line.summary;
+        let mut cells = Vec::new();
+        if self.group_by.is_some() {
+            cells.push(line.group.clone().unwrap_or_default());
+        }
+        cells.push(line.column.clone());
+        cells.push(s.count.to_string());
+        for value in [s.min, s.max, s.mean, s.median] {
+            cells.push(number(value));
+        }
+        cells.push(s.stddev.map(number).unwrap_or_default());
+        cells
+    }
+}
+
+// 最多保留三位小数，并去掉末尾多余的 0
+fn number(value: f64) -> String {
+    let text = format!("{value:.3}");
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    if text == "-0" {
+        "0".to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PENGUINS: &str = "\
+common name,length (cm)
+Little penguin,33
+Little penguin,30
+Yellow-eyed penguin,65
+Invalid,data
+";
+
+    fn write(report: &Report, format: Format) -> String {
+        let mut out = Vec::new();
+        report.write(format, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn summary_statistics() {
+        let s = Summary::of(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]).unwrap();
+        assert_eq!(
+            (8, 2.0, 9.0, 5.0, 4.5),
+            (s.count, s.min, s.max, s.mean, s.median)
+        );
+        assert_eq!("2.138", number(s.stddev.unwrap()));
+        assert_eq!(None, Summary::of(&[1.0]).unwrap().stddev);
+        assert_eq!(None, Summary::of(&[]));
+    }
+
+    #[test]
+    fn reports_whole_dataset_and_warnings() {
+        let mut data = Dataset::new();
+        assert!(data.add("penguins.csv", PENGUINS).is_empty());
+        let report = data.summarize(None).unwrap();
+
+        assert_eq!(
+            "\
+column       count  min  max    mean  median  stddev
+length (cm)      3   30   65  42.667      33  19.399
+",
+            write(&report, Format::Table)
+        );
+        assert_eq!(1, report.warnings.len());
+        assert_eq!(
+            "penguins.csv: line 5, column 9: column 'length (cm)': can't parse \"data\": invalid float literal",
+            report.warnings[0].to_string()
+        );
+    }
+
+    #[test]
+    fn groups_and_csv_output() {
+        let mut data = Dataset::new();
+        data.add("a.csv", PENGUINS);
+        let warnings = data.add(
+            "b.csv",
+            "common name,length (cm)\n\"Fiordland, NZ\",60\nbad\n",
+        );
+        assert_eq!(1, warnings.len());
+        let report = data.summarize(Some("common name")).unwrap();
+
+        assert_eq!(
+            "\
+common name,column,count,min,max,mean,median,stddev\r
+\"Fiordland, NZ\",length (cm),1,60,60,60,60,\r
+Little penguin,length (cm),2,30,33,31.5,31.5,2.121\r
+Yellow-eyed penguin,length (cm),1,65,65,65,65,\r
+",
+            write(&report, Format::Csv)
+        );
+        assert!(data.summarize(Some("colony")).is_err());
+    }
+}
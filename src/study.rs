@@ -0,0 +1,107 @@
+// 最早学 Rust 时写的代码：`hello_world xiamawei` 运行原来的逐行解析，
+// `hello_world xiamawei --csv` 运行改用 csv 模块的版本；新的命令行工具在 main.rs 里
+
+use hello_world::csv;
+
+// 两个版本读的是同一份企鹅数据
+const PENGUIN_DATA: &str = "\
+    common name,length (cm)
+    Little penguin,33
+    Yellow-eyed penguin,65
+    Fiordland penguin,60
+    Invalid,data
+    ,
+    ";
+
+// main 里暂时注释掉了对它的调用
+#[allow(dead_code)]
+fn greet_world() {
+    let southern_germany = "Grüß Gott!";
+    let chinese = "世界你好！";
+    let english = "World, hello!";
+    let regions = [southern_germany, chinese, english];
+    // 不能直接进行循环，需要变成迭代器(.iter() 方法)
+    for region in regions.iter() {
+        // 宏操作符（特殊类型函数）: !
+        // 占位符：{}
+        println!("{}", &region)
+    }
+}
+
+// 保留原来的写法，不按 clippy 的建议把 len() == 0 改成 is_empty()
+#[allow(clippy::len_zero)]
+fn xiamawei() {
+    let penguin_data = PENGUIN_DATA;
+
+    let records = penguin_data.lines();
+    // TODO: 不懂为什么用 enumerate
+    for (i, record) in records.enumerate() {
+        if i == 0 || record.trim().len() == 0 {
+            continue;
+        }
+        // 声明一个 fields 变量，类型是 Vec
+        // Vec 是 vector 的缩写，是一个可伸缩的集合类型，可以认为是一个动态数组
+        // <_> 表示 Vec 中的元素类型由编译器自行推断，在很多场景下，都会帮我们省却不少功夫
+        let fields: Vec<_> = record.split(',').map(|field| field.trim()).collect();
+        // if cfg!(debug_assertions)，说明紧跟其后的输出（打印）只在 debug 模式下生效。
+        if cfg!(debug_assertions) {
+            // 输出到标准错误输出
+            // {:?} 啥意思，是跟 eprintln 绑定的吗，应该不是
+            // 是执行输出引用（不一定）对象
+            eprintln!("debug: {:?} -> {:?}", record, fields);
+        }
+        let name = fields[0];
+        // 1. 尝试把 fields[1] 的值转换为 f32 类型的浮点数，如果成功，则把 f32 值赋给 length 变量
+        // 2. If let 是一个匹配表达式，用来从 = 右边的结果中，匹配出 length 的值:
+        //  1) 当 = 右边的表达式执行成功，则会返回一个 Ok(f32) 的类型，若失败，则会返回一个 Err(e) 类型
+        //     if let 的作用就是仅匹配 Ok 也就是成功的情况，如果是错误，就直接忽略
+        //  2) 同时 if let 还会做一次解构匹配，通过 Ok(length) 去匹配右边的 Ok(f32)，最终把相应的 f32 值赋给 length
+        //  3) 当然你也可以忽略成功的情况，用 if let Err(e) = fields[1].parse::<f32>() {...}匹配出错误，然后打印出来，但是没用
+        // Ok 是关键字吗?
+        // 是枚举 Result::Ok
+        if let Ok(length) = fields[1].parse::<f32>() {
+            println!("{}, {}cm", name, length)
+        }
+    }
+}
+
+// 原来的 main：greet_world 的调用被注释掉了，只运行 xiamawei
+pub fn main() {
+    // greet_world();
+    xiamawei();
+}
+
+// 企鹅的名字和身长，由 csv 模块按表头里的列名取值并转换类型
+struct Penguin {
+    name: String,
+    length: f32,
+}
+
+impl csv::FromRecord for Penguin {
+    fn from_record(row: &csv::Row) -> Result<Penguin, csv::Error> {
+        // ? 会把转换失败的错误（带着行号和列号）直接返回给调用方
+        Ok(Penguin {
+            name: row.get("common name")?,
+            length: row.get("length (cm)")?,
+        })
+    }
+}
+
+// 同样的数据改用 csv 模块读取：`hello_world xiamawei --csv` 运行它
+pub fn xiamawei_csv() {
+    // 每行前面的缩进不是数据的一部分，所以打开 trim
+    let mut reader = csv::Reader::new(PENGUIN_DATA).trim(true);
+    // if cfg!(debug_assertions)，说明紧跟其后的输出（打印）只在 debug 模式下生效
+    if cfg!(debug_assertions) {
+        if let Ok(headers) = reader.headers() {
+            eprintln!("debug: columns {:?}", headers.names());
+        }
+    }
+    for penguin in reader.deserialize::<Penguin>() {
+        match penguin {
+            Ok(penguin) => println!("{}, {}cm", penguin.name, penguin.length),
+            // 原来的版本用 if let Ok(..) 把解析失败的行悄悄跳过了，这里把出错的位置报告出来
+            Err(err) => eprintln!("error: {err}"),
+        }
+    }
+}